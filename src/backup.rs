@@ -0,0 +1,293 @@
+use std::{
+    io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::battlebit::State;
+
+const SNAPSHOT_FILE: &str = "settings.toml";
+/// Where the snapshot was taken from, as given by [`crate::storage::Storage::source`].
+const SOURCE_FILE: &str = "source";
+
+/// A backup found by [`list`].
+pub struct Backup {
+    pub id: String,
+    pub path: PathBuf,
+    pub values: usize,
+    /// Where it was taken from, unknown for backups made before this was recorded.
+    pub source: Option<String>,
+}
+
+/// Where backups are kept, `%APPDATA%` on Windows and the XDG data directory everywhere else.
+pub fn backups_dir() -> io::Result<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+    };
+
+    let base = base.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not find a directory to keep backups in",
+        )
+    })?;
+
+    Ok(base.join("bbr-settings-editor").join("backups"))
+}
+
+/// The snapshot file of backup `id`.
+pub fn snapshot_path(id: &str) -> io::Result<PathBuf> {
+    Ok(backups_dir()?.join(id).join(SNAPSHOT_FILE))
+}
+
+/// Formats a unix timestamp as `YYYYMMDD-HHMMSS` (UTC).
+fn timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+
+    // Howard Hinnant's days-to-civil conversion.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Snapshots every current value, byte-for-byte, into a new timestamped backup and returns its id.
+pub fn create(state: &State) -> Result<String, Box<dyn std::error::Error>> {
+    let dir = backups_dir()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let mut id = timestamp(now);
+    let mut n = 1;
+    while dir.join(&id).exists() {
+        n += 1;
+        id = format!("{}-{}", timestamp(now), n);
+    }
+
+    let path = dir.join(&id);
+    std::fs::create_dir_all(&path)?;
+    std::fs::write(path.join(SNAPSHOT_FILE), state.to_lossless_toml(None)?)?;
+    std::fs::write(path.join(SOURCE_FILE), state.storage.source())?;
+
+    Ok(id)
+}
+
+/// Every backup taken so far, oldest first.
+pub fn list() -> io::Result<Vec<Backup>> {
+    let dir = backups_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let snapshot = match std::fs::read_to_string(path.join(SNAPSHOT_FILE)) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+
+        let values = match snapshot.parse::<toml::Table>() {
+            Ok(t) => t.len(),
+            Err(e) => {
+                tracing::warn!("Skipping unreadable backup `{}`: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        backups.push(Backup {
+            id: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            source: std::fs::read_to_string(path.join(SOURCE_FILE)).ok(),
+            path,
+            values,
+        });
+    }
+
+    backups.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(backups)
+}
+
+/// A backup that was taken from different settings than the ones it is being restored into.
+#[derive(Debug)]
+pub struct OtherSource {
+    pub id: String,
+    pub source: String,
+    pub current: String,
+}
+
+impl std::fmt::Display for OtherSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Backup `{}` was taken from `{}`, not `{}`, restoring it would replace these settings with those",
+            self.id, self.source, self.current
+        )
+    }
+}
+
+impl std::error::Error for OtherSource {}
+
+/// Where backup `id` was taken from, if it was recorded.
+pub fn source(id: &str) -> io::Result<Option<String>> {
+    match std::fs::read_to_string(backups_dir()?.join(id).join(SOURCE_FILE)) {
+        Ok(source) => Ok(Some(source)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Puts the settings back exactly as they were in backup `id`, including deleting values created since.
+///
+/// As that deletes everything the backup doesn't have, restoring a backup taken from somewhere else, e.g.
+/// another prefix, is refused unless `any_source` is set.
+pub fn restore(
+    state: &mut State,
+    id: &str,
+    any_source: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = snapshot_path(id)?;
+    let snapshot = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read backup `{}`: {}", id, e))?;
+
+    let current = state.storage.source();
+    match source(id)? {
+        Some(source) if source != current && !any_source => {
+            return Err(OtherSource {
+                id: id.to_string(),
+                source,
+                current,
+            }
+            .into())
+        }
+        _ => {}
+    }
+
+    let table = snapshot.parse::<toml::Table>()?;
+    let stale: Vec<String> = state
+        .original_registry_keys
+        .iter()
+        .filter(|name| !table.contains_key(name.as_str()))
+        .cloned()
+        .collect();
+
+    for name in stale {
+        state.delete_registry(&name);
+    }
+    state.from_toml(toml::Value::Table(table))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battlebit::{RegType, RegValue},
+        storage::MemoryStorage,
+    };
+
+    fn state(values: Vec<(&str, i32)>) -> State {
+        let values = values
+            .into_iter()
+            .map(|(name, value)| {
+                let value = RegValue {
+                    bytes: value.to_le_bytes().to_vec(),
+                    vtype: RegType::REG_DWORD,
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        State::new(Box::new(MemoryStorage::new(values))).unwrap()
+    }
+
+    fn names(state: &State) -> Vec<String> {
+        state
+            .storage
+            .values()
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn timestamps_are_utc_dates() {
+        assert_eq!(timestamp(0), "19700101-000000");
+        assert_eq!(timestamp(951_782_400 + 3_723), "20000229-010203");
+        assert_eq!(timestamp(1_704_067_199), "20231231-235959");
+    }
+
+    // The only test that touches the backup directory, as it is found through the environment.
+    #[test]
+    fn backups_are_listed_and_restored() {
+        let dir = std::env::temp_dir().join(format!(
+            "bbr-settings-editor-backups-{}",
+            std::process::id()
+        ));
+        std::env::set_var("XDG_DATA_HOME", &dir);
+        std::env::set_var("APPDATA", &dir);
+        assert!(list().unwrap().is_empty());
+
+        let mut state = state(vec![("Jump_key_h1", 32), ("Crouch_key_h2", 99)]);
+        let first = create(&state).unwrap();
+        let second = create(&state).unwrap();
+        assert_eq!(first.len(), "YYYYMMDD-HHMMSS".len());
+        assert_ne!(first, second);
+
+        let backups = list().unwrap();
+        let ids: Vec<&str> = backups.iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(ids, [first.as_str(), second.as_str()]);
+        assert_eq!(backups[0].values, 2);
+        assert_eq!(backups[0].source.as_deref(), Some("memory"));
+
+        state
+            .from_toml_str("[Sensitivity]\ntyp = \"int\"\nvalue = 7\n")
+            .unwrap();
+        state.delete_registry("Crouch_key_h2");
+        state.save_registry().unwrap();
+        let mut state = State::new(Box::new(MemoryStorage::new(
+            state.storage.values().unwrap(),
+        )))
+        .unwrap();
+
+        restore(&mut state, &first, false).unwrap();
+        state.save_registry().unwrap();
+        let mut restored = names(&state);
+        restored.sort();
+        assert_eq!(restored, ["Crouch_key_h2", "Jump_key_h1"]);
+
+        std::fs::write(
+            backups_dir().unwrap().join(&second).join(SOURCE_FILE),
+            "elsewhere",
+        )
+        .unwrap();
+        let error = restore(&mut state, &second, false).unwrap_err();
+        assert!(error.downcast_ref::<OtherSource>().is_some(), "{}", error);
+        restore(&mut state, &second, true).unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,56 +1,273 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use toml::Value;
-use winreg::{
-    enums::{HKEY_CURRENT_USER, KEY_SET_VALUE},
-    RegKey, RegValue,
+
+use crate::{
+    error::{ErrorKind, SettingsError},
+    filters::{Filter, FilterVariant},
+    keycode,
+    storage::Storage,
 };
 
-const BBR_REG_SUBKEY: &str = "SOFTWARE\\BattleBitDevTeam\\BattleBit";
+pub const BBR_REG_SUBKEY: &str = "SOFTWARE\\BattleBitDevTeam\\BattleBit";
 
-#[derive(Debug)]
+/// Unity's PlayerPrefs hash of a key name (djb2, xor variant).
+pub fn player_prefs_hash(name: &str) -> u32 {
+    name.bytes()
+        .fold(5381u32, |hash, b| hash.wrapping_mul(33) ^ b as u32)
+}
+
+/// Strips Unity's `_h<hash>` suffix from a registry value name, leaving the PlayerPrefs key.
+pub fn player_prefs_key(regedit_name: &str) -> &str {
+    match regedit_name.rsplit_once("_h") {
+        Some((key, hash)) if !hash.is_empty() && hash.bytes().all(|b| b.is_ascii_digit()) => key,
+        _ => regedit_name,
+    }
+}
+
+/// Prefixes of the ints Unity itself stores, which aren't tagged with their type.
+const UNTAGGED_INT_PREFIXES: &[&str] = &["Screenmanager", "Unity"];
+
+fn is_untagged_int(key: &str) -> bool {
+    UNTAGGED_INT_PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+}
+
+/// Splits a registry value name into the setting name used in configs and its type.
+///
+/// Every key [`encode_player_prefs_key`] creates decodes back to the setting it was created for, but not the
+/// other way around: untagged strings such as `unity.cloud_userid` decode to the same setting as a `_str` one.
+pub fn decode_regedit_name(regedit_name: &str) -> (String, &'static str) {
+    let key = player_prefs_key(regedit_name);
+
+    if is_untagged_int(key) {
+        return (key.to_string(), "int");
+    }
+
+    let (name, tag) = match key.rsplit_once('_') {
+        Some(parts) => parts,
+        None => return (key.to_string(), "str"),
+    };
+
+    match tag {
+        "int" => (name.to_string(), "int"),
+        "float" => (name.to_string(), "float"),
+        "bool" => (name.to_string(), "bool"),
+        "str" => (name.to_string(), "str"),
+        "axis" => (key.to_string(), "axis"),
+        "key" => (key.to_string(), "key"),
+        "r" | "g" | "b" | "a" => (key.to_string(), "color"),
+        _ => (key.to_string(), "str"),
+    }
+}
+
+/// The PlayerPrefs key a new setting called `name` of type `typ` is created under.
+///
+/// Strings are always tagged, so an untagged one is only ever written under the registry name it already has.
+pub fn encode_player_prefs_key(name: &str, typ: &str) -> String {
+    match typ {
+        "int" if is_untagged_int(name) => name.to_string(),
+        "axis" | "key" | "color" => name.to_string(),
+        _ => format!("{}_{}", name, typ),
+    }
+}
+
+/// The registry value name Unity stores the PlayerPrefs key `name` under, e.g. `Name_h1234567`.
+pub fn player_prefs_name(name: &str) -> String {
+    format!("{}_h{}", name, player_prefs_hash(name))
+}
+
+/// The type of a registry value, with the same numbering as Windows.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegType {
+    REG_NONE = 0,
+    REG_SZ = 1,
+    REG_EXPAND_SZ = 2,
+    REG_BINARY = 3,
+    REG_DWORD = 4,
+    REG_DWORD_BIG_ENDIAN = 5,
+    REG_LINK = 6,
+    REG_MULTI_SZ = 7,
+    REG_RESOURCE_LIST = 8,
+    REG_FULL_RESOURCE_DESCRIPTOR = 9,
+    REG_RESOURCE_REQUIREMENTS_LIST = 10,
+    REG_QWORD = 11,
+}
+
+impl RegType {
+    pub fn from_u32(typ: u32) -> Option<Self> {
+        Some(match typ {
+            0 => RegType::REG_NONE,
+            1 => RegType::REG_SZ,
+            2 => RegType::REG_EXPAND_SZ,
+            3 => RegType::REG_BINARY,
+            4 => RegType::REG_DWORD,
+            5 => RegType::REG_DWORD_BIG_ENDIAN,
+            6 => RegType::REG_LINK,
+            7 => RegType::REG_MULTI_SZ,
+            8 => RegType::REG_RESOURCE_LIST,
+            9 => RegType::REG_FULL_RESOURCE_DESCRIPTOR,
+            10 => RegType::REG_RESOURCE_REQUIREMENTS_LIST,
+            11 => RegType::REG_QWORD,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RegType::REG_NONE => "REG_NONE",
+            RegType::REG_SZ => "REG_SZ",
+            RegType::REG_EXPAND_SZ => "REG_EXPAND_SZ",
+            RegType::REG_BINARY => "REG_BINARY",
+            RegType::REG_DWORD => "REG_DWORD",
+            RegType::REG_DWORD_BIG_ENDIAN => "REG_DWORD_BIG_ENDIAN",
+            RegType::REG_LINK => "REG_LINK",
+            RegType::REG_MULTI_SZ => "REG_MULTI_SZ",
+            RegType::REG_RESOURCE_LIST => "REG_RESOURCE_LIST",
+            RegType::REG_FULL_RESOURCE_DESCRIPTOR => "REG_FULL_RESOURCE_DESCRIPTOR",
+            RegType::REG_RESOURCE_REQUIREMENTS_LIST => "REG_RESOURCE_REQUIREMENTS_LIST",
+            RegType::REG_QWORD => "REG_QWORD",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..=11)
+            .filter_map(RegType::from_u32)
+            .find(|t| t.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// A raw registry value, independent of where it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegValue {
+    pub bytes: Vec<u8>,
+    pub vtype: RegType,
+}
+
+#[cfg(windows)]
+impl From<winreg::RegValue> for RegValue {
+    fn from(value: winreg::RegValue) -> Self {
+        RegValue {
+            bytes: value.bytes,
+            vtype: RegType::from_u32(value.vtype as u32).unwrap_or(RegType::REG_NONE),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<&RegValue> for winreg::RegValue {
+    fn from(value: &RegValue) -> Self {
+        use winreg::enums::RegType as W;
+
+        let vtype = match value.vtype {
+            RegType::REG_NONE => W::REG_NONE,
+            RegType::REG_SZ => W::REG_SZ,
+            RegType::REG_EXPAND_SZ => W::REG_EXPAND_SZ,
+            RegType::REG_BINARY => W::REG_BINARY,
+            RegType::REG_DWORD => W::REG_DWORD,
+            RegType::REG_DWORD_BIG_ENDIAN => W::REG_DWORD_BIG_ENDIAN,
+            RegType::REG_LINK => W::REG_LINK,
+            RegType::REG_MULTI_SZ => W::REG_MULTI_SZ,
+            RegType::REG_RESOURCE_LIST => W::REG_RESOURCE_LIST,
+            RegType::REG_FULL_RESOURCE_DESCRIPTOR => W::REG_FULL_RESOURCE_DESCRIPTOR,
+            RegType::REG_RESOURCE_REQUIREMENTS_LIST => W::REG_RESOURCE_REQUIREMENTS_LIST,
+            RegType::REG_QWORD => W::REG_QWORD,
+        };
+
+        winreg::RegValue {
+            bytes: value.bytes.clone(),
+            vtype,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Int(pub i32);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Float(pub f64);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Bool(pub bool);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Axis(pub i32);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Color(pub i32, pub f64); // param 1: R: 0, G: 1, B: 2, A: 3, param 2: value
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Key(pub i32);
 
+const KEY_EXPECTED: &str =
+    "a Unity key name such as `LeftShift` or `Mouse3`, a single character or `\\u` and its code in hex";
+
 impl Key {
-    pub fn to_key_ascii(&self) -> String {
-        unsafe { std::char::from_u32_unchecked(self.0 as u32) }.to_string()
-    }
-
-    pub fn from_key_ascii(key: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let key = if key.starts_with("\\u") {
-            let key = key.trim_start_matches("\\u");
-            let key = u32::from_str_radix(key, 16)?;
-            char::from_u32(key)
-                .ok_or("Failed to convert u32 to char")?
-                .to_string()
-        } else {
-            key.to_string()
-        };
+    /// Parses a key by its Unity `KeyCode` name, ignoring case, falling back to a single character or `\u`
+    /// followed by the code in hex, e.g. `\u0130`. Codes are 32-bit, so `\uffffffff` is -1.
+    pub fn from_name(key: &str) -> Result<Self, SettingsError> {
+        if let Some(code) = keycode::from_name(key) {
+            return Ok(Key(code));
+        }
+
+        let invalid = || SettingsError::invalid_value(KEY_EXPECTED, format!("`{}`", key));
+
+        if let Some(code) = key.strip_prefix("\\u") {
+            // Read as the bits of the code, so that every code `name` writes, even a negative one, reads back.
+            return u32::from_str_radix(code, 16)
+                .ok()
+                .filter(|_| !code.starts_with('+'))
+                .map(|code| Key(code as i32))
+                .ok_or_else(invalid);
+        }
+
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Key(c as i32)),
+            _ => Err(invalid()),
+        }
+    }
 
-        let key = key.chars().next().ok_or("No char found")? as i32;
-        Ok(Key(key))
+    /// The key's Unity `KeyCode` name, e.g. `LeftShift` or `Mouse1`, or `\u` and its code for unknown keys.
+    pub fn name(&self) -> String {
+        match keycode::name(self.0) {
+            Some(name) => name.to_string(),
+            None => format!("\\u{:04x}", self.0 as u32),
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Str(pub String);
 
-#[derive(Debug)]
+/// A value kept exactly as it was found in the registry, used for lossless exports and `.reg` imports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raw(pub RegValue);
+
+impl Raw {
+    pub fn to_hex(&self) -> String {
+        self.0.bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn from_hex(vtype: RegType, hex: &str) -> Result<Self, SettingsError> {
+        let invalid = || SettingsError::invalid_value("an even number of hex digits", format!("`{}`", hex));
+
+        if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+            return Err(invalid());
+        }
+
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+
+        Ok(Raw(RegValue { bytes, vtype }))
+    }
+}
+
+/// A decoded setting.
+#[derive(Debug, Clone, PartialEq)]
 pub enum BattlebitType {
     Int(Int),
     Float(Float),
@@ -59,104 +276,174 @@ pub enum BattlebitType {
     Color(Color),
     Key(Key),
     Str(Str),
+    Raw(Raw),
 }
 
-impl BattlebitType {
-    pub fn from_reg(
-        key: String,
-        value: RegValue,
-    ) -> Result<(String, BattlebitType), Box<dyn std::error::Error>> {
-        let key_parts: Vec<&str> = key.split('_').collect();
-        let length = key_parts.len();
-
-        let typ = key_parts[length - 2];
-        let name = key_parts[..length - 2].join("_");
-
-        if typ.starts_with("Screenmanager") {
-            let value = i32::from_le_bytes(match value.bytes.to_vec().try_into() {
-                Ok(v) => v,
-                Err(_) => return Ok((name, BattlebitType::Int(Int(0)))),
-            });
-            return Ok((name, BattlebitType::Int(Int(value as i32))));
+/// Reads a fixed size value, warning instead of failing when the registry holds something else.
+fn fixed_bytes<const N: usize>(regedit_name: &str, value: &RegValue) -> Option<[u8; N]> {
+    match value.bytes.as_slice().try_into() {
+        Ok(bytes) => Some(bytes),
+        Err(_) => {
+            tracing::warn!(
+                "`{}` is {} bytes long instead of {}, export with `--lossless` to keep it intact",
+                regedit_name,
+                value.bytes.len(),
+                N
+            );
+            None
         }
+    }
+}
+
+impl BattlebitType {
+    /// Decodes a raw registry value, returning the setting's name along with its value.
+    pub fn from_reg(key: String, value: RegValue) -> Result<(String, BattlebitType), SettingsError> {
+        let (name, typ) = decode_regedit_name(&key);
 
         match typ {
             "int" => {
-                let value = i32::from_le_bytes(match value.bytes.to_vec().try_into() {
-                    Ok(v) => v,
-                    Err(_) => return Ok((name, BattlebitType::Int(Int(0)))),
-                });
-                Ok((name, BattlebitType::Int(Int(value as i32))))
+                let value = fixed_bytes(&key, &value).map(i32::from_le_bytes);
+                Ok((name, BattlebitType::Int(Int(value.unwrap_or(0)))))
             }
             "float" => {
-                let value = f64::from_le_bytes(match value.bytes.to_vec().try_into() {
-                    Ok(v) => v,
-                    Err(_) => return Ok((name, BattlebitType::Float(Float(0.0)))),
-                });
-                Ok((name, BattlebitType::Float(Float(value as f64))))
+                let value = fixed_bytes(&key, &value).map(f64::from_le_bytes);
+                Ok((name, BattlebitType::Float(Float(value.unwrap_or(0.0)))))
             }
             "bool" => {
-                let value = i32::from_le_bytes(match value.bytes.to_vec().try_into() {
-                    Ok(v) => v,
-                    Err(_) => return Ok((name, BattlebitType::Bool(Bool(false)))),
-                });
-                Ok((name, BattlebitType::Bool(Bool(value == 1))))
+                let value = fixed_bytes(&key, &value).map(i32::from_le_bytes);
+                Ok((name, BattlebitType::Bool(Bool(value == Some(1)))))
             }
             "axis" => {
-                let value = i32::from_le_bytes(match value.bytes.to_vec().try_into() {
-                    Ok(v) => v,
-                    Err(_) => return Ok((name + "_axis", BattlebitType::Axis(Axis(0)))),
-                });
-                Ok((name + "_axis", BattlebitType::Axis(Axis(value as i32))))
+                let value = fixed_bytes(&key, &value).map(i32::from_le_bytes);
+                Ok((name, BattlebitType::Axis(Axis(value.unwrap_or(0)))))
             }
             "key" => {
-                let value = i32::from_le_bytes(match value.bytes.to_vec().try_into() {
-                    Ok(v) => v,
-                    Err(_) => return Ok((name + "_key", BattlebitType::Key(Key(0)))),
-                });
-                Ok((name + "_key", BattlebitType::Key(Key(value as i32))))
+                let value = fixed_bytes(&key, &value).map(i32::from_le_bytes);
+                Ok((name, BattlebitType::Key(Key(value.unwrap_or(0)))))
             }
-            "r" | "g" | "b" | "a" => {
-                let kv = match typ {
-                    "r" => 0,
-                    "g" => 1,
-                    "b" => 2,
-                    "a" => 3,
-                    _ => unreachable!("Invalid color type"),
+            "color" => {
+                let kv = match name.chars().last() {
+                    Some('r') => 0,
+                    Some('g') => 1,
+                    Some('b') => 2,
+                    Some('a') => 3,
+                    _ => {
+                        return Err(SettingsError::invalid_value(
+                            "a color channel ending in r, g, b or a",
+                            format!("`{}`", name),
+                        )
+                        .for_setting(&key))
+                    }
                 };
 
-                let value = f64::from_le_bytes(match value.bytes.to_vec().try_into() {
-                    Ok(v) => v,
-                    Err(_) => return Ok((name + "_" + typ, BattlebitType::Int(Int(0)))),
-                });
-                Ok((
-                    name + "_" + typ,
-                    BattlebitType::Color(Color(kv, value as f64)),
-                ))
+                match fixed_bytes(&key, &value).map(f64::from_le_bytes) {
+                    Some(value) => Ok((name, BattlebitType::Color(Color(kv, value)))),
+                    // Kept as it is, as anything else would overwrite the channel on the next import.
+                    None => Ok((name, BattlebitType::Raw(Raw(value)))),
+                }
+            }
+            // Untagged values can be ints too, e.g. ones Unity's own code stores.
+            _ if value.vtype == RegType::REG_DWORD && value.bytes.len() == 4 => {
+                let value = fixed_bytes(&key, &value).map(i32::from_le_bytes);
+                Ok((name, BattlebitType::Int(Int(value.unwrap_or(0)))))
             }
+            // PlayerPrefs strings are always `REG_BINARY`, so anything else is kept as it is.
+            _ if value.vtype != RegType::REG_BINARY => Ok((name, BattlebitType::Raw(Raw(value)))),
             _ => {
-                let value = match String::from_utf8(value.bytes.to_vec()) {
+                let bytes = value.bytes.strip_suffix(&[0]).unwrap_or(&value.bytes);
+                let value = match String::from_utf8(bytes.to_vec()) {
                     Ok(v) => v,
-                    Err(_) => String::from(""),
+                    Err(_) => {
+                        tracing::warn!(
+                            "`{}` is not valid UTF-8, export with `--lossless` to keep it intact",
+                            key
+                        );
+                        String::new()
+                    }
                 };
                 Ok((name, BattlebitType::Str(Str(value))))
             }
         }
     }
 
+    /// The setting a value stored under `regedit_name` holds, decoding it if it is `Raw`.
+    pub fn decoded(&self, regedit_name: &str) -> BattlebitType {
+        match self {
+            BattlebitType::Raw(Raw(value)) => BattlebitType::from_reg(regedit_name.to_string(), value.clone())
+                .map(|(_, value)| value)
+                .unwrap_or_else(|_| self.clone()),
+            _ => self.clone(),
+        }
+    }
+
+    /// Parses `input` as a new value of the same kind as `self`, e.g. for `set` on the command line.
+    pub fn parse_like(&self, input: &str) -> Result<BattlebitType, SettingsError> {
+        let input = input.trim();
+        let invalid = |expected| SettingsError::invalid_value(expected, format!("`{}`", input));
+
+        Ok(match self {
+            BattlebitType::Int(_) => BattlebitType::Int(Int(input.parse().map_err(|_| invalid("an int"))?)),
+            BattlebitType::Float(_) => {
+                BattlebitType::Float(Float(input.parse().map_err(|_| invalid("a float"))?))
+            }
+            BattlebitType::Bool(_) => BattlebitType::Bool(Bool(
+                match input.to_lowercase().as_str() {
+                    "true" | "1" | "yes" | "on" => true,
+                    "false" | "0" | "no" | "off" => false,
+                    _ => return Err(invalid("a bool")),
+                },
+            )),
+            BattlebitType::Axis(_) => BattlebitType::Axis(Axis(input.parse().map_err(|_| invalid("an int"))?)),
+            BattlebitType::Color(c) => {
+                let value = input
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| invalid("a finite float"))?;
+                BattlebitType::Color(Color(c.0, value))
+            }
+            BattlebitType::Key(_) => BattlebitType::Key(Key::from_name(input)?),
+            BattlebitType::Str(_) => BattlebitType::Str(Str(input.to_string())),
+            BattlebitType::Raw(_) => return Err(invalid("a decoded setting, raw values can't be set")),
+        })
+    }
+
+    /// The value as it is written in TOML configs.
     pub fn to_toml_value(&self) -> Value {
         match self {
             BattlebitType::Int(i) => Value::Integer(i.0 as i64),
             BattlebitType::Float(f) => Value::Float(f.0),
             BattlebitType::Bool(b) => Value::Boolean(b.0),
             BattlebitType::Axis(a) => Value::Integer(a.0 as i64),
-            BattlebitType::Color(c) => Value::Float(c.1 as f64),
-            BattlebitType::Key(k) => Value::String(k.to_key_ascii()),
+            BattlebitType::Color(c) => Value::Float(c.1),
+            BattlebitType::Key(k) => Value::String(k.name()),
             BattlebitType::Str(s) => Value::String(s.0.clone()),
+            BattlebitType::Raw(r) => Value::String(r.to_hex()),
+        }
+    }
+
+    /// Formats the value for people rather than configs: on a single line, with keys by name.
+    pub fn to_display_string(&self) -> String {
+        match self {
+            BattlebitType::Key(k) => k.name(),
+            BattlebitType::Str(s) => format!("{:?}", s.0),
+            _ => self.to_toml_value().to_string(),
+        }
+    }
+
+    /// How far a numeric value moved from `old`, if both are numbers of the same kind.
+    pub fn delta(&self, old: &BattlebitType) -> Option<f64> {
+        match (old, self) {
+            (BattlebitType::Int(a), BattlebitType::Int(b)) => Some(b.0 as f64 - a.0 as f64),
+            (BattlebitType::Float(a), BattlebitType::Float(b)) => Some(b.0 - a.0),
+            (BattlebitType::Axis(a), BattlebitType::Axis(b)) => Some(b.0 as f64 - a.0 as f64),
+            (BattlebitType::Color(a), BattlebitType::Color(b)) if a.0 == b.0 => Some(b.1 - a.1),
+            _ => None,
         }
     }
 
-    pub fn to_reg_type_str(&self) -> &str {
+    /// The name of the value's type, as used in TOML configs and by filters.
+    pub fn to_reg_type_str(&self) -> &'static str {
         match self {
             BattlebitType::Int(_) => "int",
             BattlebitType::Float(_) => "float",
@@ -165,66 +452,99 @@ impl BattlebitType {
             BattlebitType::Color(_) => "color",
             BattlebitType::Key(_) => "key",
             BattlebitType::Str(_) => "str",
+            BattlebitType::Raw(_) => "raw",
         }
     }
 
+    /// Encodes the value with the same registry type and layout Unity's PlayerPrefs uses.
+    ///
+    /// Ints (and everything the game stores as one) are 4 byte `REG_DWORD`s and strings are NUL terminated
+    /// UTF-8 in a `REG_BINARY`. Floats are 8 byte doubles tagged `REG_DWORD`, which regedit shows as an
+    /// invalid DWORD, but it is what Unity writes and reads back.
     pub fn to_reg_value(&self) -> RegValue {
         match self {
             BattlebitType::Int(i) => RegValue {
                 bytes: i.0.to_le_bytes().to_vec(),
-                vtype: winreg::enums::RegType::REG_DWORD,
+                vtype: RegType::REG_DWORD,
             },
             BattlebitType::Float(f) => RegValue {
                 bytes: f.0.to_le_bytes().to_vec(),
-                vtype: winreg::enums::RegType::REG_DWORD,
+                vtype: RegType::REG_DWORD,
             },
             BattlebitType::Bool(b) => RegValue {
-                bytes: [b.0 as u8].to_vec(),
-                vtype: winreg::enums::RegType::REG_DWORD,
+                bytes: (b.0 as i32).to_le_bytes().to_vec(),
+                vtype: RegType::REG_DWORD,
             },
             BattlebitType::Axis(a) => RegValue {
                 bytes: a.0.to_le_bytes().to_vec(),
-                vtype: winreg::enums::RegType::REG_DWORD,
+                vtype: RegType::REG_DWORD,
             },
             BattlebitType::Color(c) => RegValue {
                 bytes: c.1.to_le_bytes().to_vec(),
-                vtype: winreg::enums::RegType::REG_DWORD,
+                vtype: RegType::REG_DWORD,
             },
             BattlebitType::Key(k) => RegValue {
                 bytes: k.0.to_le_bytes().to_vec(),
-                vtype: winreg::enums::RegType::REG_DWORD,
+                vtype: RegType::REG_DWORD,
             },
             BattlebitType::Str(s) => RegValue {
-                bytes: s.0.as_bytes().to_vec(),
-                vtype: winreg::enums::RegType::REG_BINARY,
+                bytes: s.0.bytes().chain(std::iter::once(0)).collect(),
+                vtype: RegType::REG_BINARY,
             },
+            BattlebitType::Raw(r) => r.0.clone(),
         }
     }
 }
 
+/// The registry value a setting resolved to.
+#[derive(Debug, PartialEq)]
+pub enum Resolved {
+    Existing(String),
+    /// Nothing in the registry matched, so the setting will be created under this name.
+    New(String),
+}
+
+/// What saving would do to a single registry value, with `Raw` values decoded into the settings they hold.
+#[derive(Debug)]
+pub enum Change {
+    Added(BattlebitType),
+    Changed(BattlebitType, BattlebitType),
+    Unchanged(BattlebitType),
+    Deleted(BattlebitType),
+}
+
+#[derive(Debug)]
+pub struct PendingChange {
+    pub regedit_name: String,
+    /// The decoded setting name, as used in configs.
+    pub name: String,
+    pub change: Change,
+}
+
+/// The settings in a [`Storage`], along with the changes queued up for it.
+///
+/// Changes are only written by [`State::save_registry`].
 pub struct State {
+    pub storage: Box<dyn Storage>,
+    /// The registry names of every value in the storage when it was loaded.
     pub original_registry_keys: Vec<String>,
+    /// The decoded settings when the storage was loaded, by setting name.
     pub registry: HashMap<String, BattlebitType>,
+    /// Values to write, by registry name.
     pub updated_registry: HashMap<String, BattlebitType>,
+    /// Values to delete, by registry name.
+    pub deleted_registry: HashSet<String>,
 }
 
 impl State {
-    pub fn new() -> std::io::Result<Self> {
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-
-        let battlebit = hkcu.open_subkey(BBR_REG_SUBKEY)?;
-
-        let registry = battlebit
-            .enum_values()
-            .filter_map(|x| {
-                let (name, value) = match x {
-                    Ok(v) => v,
-                    Err(e) => {
-                        tracing::error!("Failed to enumerate registry value: {}", e);
-                        return None;
-                    }
-                };
-
+    /// Loads and decodes every setting in `storage`.
+    pub fn new(storage: Box<dyn Storage>) -> std::io::Result<Self> {
+        let values = storage.values()?;
+        let original_registry_keys = values.iter().map(|(name, _)| name.clone()).collect();
+
+        let registry = values
+            .into_iter()
+            .filter_map(|(name, value)| {
                 if !name.is_empty() {
                     let value = match BattlebitType::from_reg(name, value) {
                         Ok(v) => v,
@@ -240,47 +560,282 @@ impl State {
             })
             .collect();
 
-        let state = State {
-            original_registry_keys: battlebit
-                .enum_values()
-                .map(|x| x.expect("Failed to enumerate registry value").0)
-                .collect(),
+        Ok(State {
+            storage,
+            original_registry_keys,
             registry,
             updated_registry: HashMap::new(),
-        };
+            deleted_registry: HashSet::new(),
+        })
+    }
+
+    /// Splits a registry value name into its setting name and type, like [`decode_regedit_name`], but going by
+    /// the stored value for untagged names, which can hold ints as well as strings, and for values kept raw.
+    pub fn decode_registry_name(&self, regedit_name: &str) -> (String, &'static str) {
+        let (name, typ) = decode_regedit_name(regedit_name);
 
-        Ok(state)
+        match self.registry.get(&name) {
+            Some(value) if player_prefs_key(regedit_name) == name => (name, value.to_reg_type_str()),
+            _ => (name, typ),
+        }
+    }
+
+    /// Finds the registry value a setting is stored under.
+    ///
+    /// Only values that decode to exactly `name` and the type of `typ` are considered. If there are none,
+    /// the name Unity would create is returned, and if there are several it is an error rather than a guess.
+    pub fn resolve_regedit_name(&self, name: &str, typ: &BattlebitType) -> Result<Resolved, SettingsError> {
+        let typ = typ.to_reg_type_str();
+
+        let matches: Vec<&String> = self
+            .original_registry_keys
+            .iter()
+            .filter(|raw| self.decode_registry_name(raw) == (name.to_string(), typ))
+            .collect();
+
+        match matches.as_slice() {
+            [] => Ok(Resolved::New(player_prefs_name(&encode_player_prefs_key(
+                name, typ,
+            )))),
+            [raw] => Ok(Resolved::Existing(raw.to_string())),
+            _ => Err(SettingsError::new(ErrorKind::Ambiguous {
+                candidates: matches.into_iter().cloned().collect(),
+            })
+            .for_setting(name)),
+        }
     }
 
-    pub fn resolve_regedit_name(&self, name: String, typ: &BattlebitType) -> String {
-        let mut name = match typ {
-            BattlebitType::Color(_) => name,
-            BattlebitType::Key(_) => name,
-            BattlebitType::Axis(_) => name,
-            _ => name + "_" + typ.to_reg_type_str(),
+    /// Queues setting `name` to be written with `value`, under the registry name it resolves to.
+    pub fn update_registry(&mut self, name: &str, value: BattlebitType) -> Result<(), SettingsError> {
+        let name = match self.resolve_regedit_name(name, &value)? {
+            Resolved::Existing(name) => name,
+            Resolved::New(regedit_name) => {
+                tracing::warn!(
+                    "`{}` is not in the registry yet, it will be created as `{}`",
+                    name,
+                    regedit_name
+                );
+                regedit_name
+            }
         };
+        self.updated_registry.insert(name, value);
+        Ok(())
+    }
+
+    /// Compares the pending updates and deletions against what is currently stored, sorted by setting name.
+    pub fn pending_changes(&self) -> std::io::Result<Vec<PendingChange>> {
+        let stored: HashMap<String, RegValue> = self.storage.values()?.into_iter().collect();
+        let decode = |regedit_name: &str, value: &RegValue| BattlebitType::Raw(Raw(value.clone())).decoded(regedit_name);
+
+        let mut changes = Vec::new();
+
+        for (regedit_name, value) in &self.updated_registry {
+            let new = value.decoded(regedit_name);
+            let change = match stored.get(regedit_name) {
+                Some(old) if *old == value.to_reg_value() => Change::Unchanged(new),
+                Some(old) => Change::Changed(decode(regedit_name, old), new),
+                None => Change::Added(new),
+            };
+
+            changes.push(PendingChange {
+                regedit_name: regedit_name.clone(),
+                name: decode_regedit_name(regedit_name).0,
+                change,
+            });
+        }
+
+        for regedit_name in &self.deleted_registry {
+            if self.updated_registry.contains_key(regedit_name) {
+                continue;
+            }
 
-        for value in &self.original_registry_keys {
-            if value.contains(&name) {
-                name = value.clone();
-                break;
+            if let Some(old) = stored.get(regedit_name) {
+                changes.push(PendingChange {
+                    regedit_name: regedit_name.clone(),
+                    name: decode_regedit_name(regedit_name).0,
+                    change: Change::Deleted(decode(regedit_name, old)),
+                });
             }
         }
-        name
+
+        changes.sort_by(|a, b| a.name.cmp(&b.name).then(a.regedit_name.cmp(&b.regedit_name)));
+        Ok(changes)
     }
 
-    pub fn update_registry(&mut self, name: &str, value: BattlebitType) {
-        let name = self.resolve_regedit_name(name.to_owned(), &value);
-        self.updated_registry.insert(name, value);
+    /// Marks a registry value for deletion on the next save, unless it is also updated.
+    pub fn delete_registry(&mut self, regedit_name: &str) {
+        self.deleted_registry.insert(regedit_name.to_string());
+    }
+
+    /// Marks every setting matching `include`, or every setting if it is `None`, for deletion so the game
+    /// recreates them with their defaults. Returns how many there are.
+    pub fn reset(&mut self, include: Option<&[FilterVariant]>) -> usize {
+        let names: Vec<String> = self
+            .original_registry_keys
+            .iter()
+            .filter(|raw| match include {
+                Some(include) => {
+                    let (name, typ) = self.decode_registry_name(raw);
+                    include.filter(&name, typ)
+                }
+                None => true,
+            })
+            .cloned()
+            .collect();
+
+        for name in &names {
+            self.delete_registry(name);
+        }
+
+        names.len()
     }
 
-    pub fn save_registry(&self) -> std::io::Result<()> {
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-        let battlebit = hkcu.open_subkey_with_flags(BBR_REG_SUBKEY, KEY_SET_VALUE)?;
+    /// Writes every queued change to the storage, then checks it reads back the same.
+    pub fn save_registry(&mut self) -> std::io::Result<()> {
+        for name in &self.deleted_registry {
+            if !self.updated_registry.contains_key(name) {
+                self.storage.delete_value(name)?;
+            }
+        }
+        let current: HashMap<String, RegValue> = self.storage.values()?.into_iter().collect();
         for (name, value) in &self.updated_registry {
             let reg_value = value.to_reg_value();
-            battlebit.set_raw_value(name, &reg_value)?;
+            if current.get(name) != Some(&reg_value) {
+                self.storage.set_value(name, &reg_value)?;
+            }
         }
+        self.storage.flush()?;
+        self.verify_registry()
+    }
+
+    /// Re-reads every value that was written from the storage and checks it is stored and decodes exactly as
+    /// intended.
+    pub fn verify_registry(&self) -> std::io::Result<()> {
+        let stored: HashMap<String, RegValue> = self.storage.values()?.into_iter().collect();
+        let mut mismatches = Vec::new();
+
+        for name in &self.deleted_registry {
+            if !self.updated_registry.contains_key(name) && stored.contains_key(name) {
+                mismatches.push(format!("`{}` was not deleted", name));
+            }
+        }
+
+        for (name, value) in &self.updated_registry {
+            let intended = value.to_reg_value();
+
+            let actual = match stored.get(name) {
+                Some(actual) => actual,
+                None => {
+                    mismatches.push(format!("`{}` is missing", name));
+                    continue;
+                }
+            };
+
+            if *actual != intended {
+                mismatches.push(format!(
+                    "`{}` was stored as {} bytes of {} instead of {} bytes of {}",
+                    name,
+                    actual.bytes.len(),
+                    actual.vtype.name(),
+                    intended.bytes.len(),
+                    intended.vtype.name()
+                ));
+                continue;
+            }
+
+            if let BattlebitType::Raw(_) = value {
+                continue;
+            }
+
+            match BattlebitType::from_reg(name.clone(), actual.clone()) {
+                Ok((_, decoded)) if decoded == *value => {}
+                Ok((_, decoded)) => mismatches.push(format!(
+                    "`{}` reads back as {:?} instead of {:?}",
+                    name, decoded, value
+                )),
+                Err(e) => mismatches.push(format!("`{}` can't be read back: {}", name, e)),
+            }
+        }
+
+        if !mismatches.is_empty() {
+            mismatches.sort();
+            return Err(std::io::Error::other(format!(
+                "Failed to verify the saved settings:\n  {}",
+                mismatches.join("\n  ")
+            )));
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_parsed_by_name_character_or_code() {
+        assert_eq!(Key::from_name("LeftShift").unwrap(), Key(304));
+        assert_eq!(Key::from_name("mouse1").unwrap(), Key(324));
+        assert_eq!(Key::from_name("é").unwrap(), Key(0xe9));
+        assert_eq!(Key::from_name("\\u0130").unwrap(), Key(0x130));
+        assert_eq!(Key::from_name("\\uffffffff").unwrap(), Key(-1));
+    }
+
+    #[test]
+    fn keys_out_of_range_are_rejected() {
+        for key in ["\\u100000000", "\\u", "\\u+1", "\\u-1", "\\uzz", "ab"] {
+            assert!(Key::from_name(key).is_err(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn key_names_round_trip() {
+        for code in [0, 97, 304, 0x130, 1000, -1, i32::MIN, i32::MAX] {
+            let name = Key(code).name();
+            assert_eq!(Key::from_name(&name).unwrap(), Key(code), "{}", name);
+        }
+        assert_eq!(Key(1000).name(), "\\u03e8");
+        assert_eq!(Key(-1).name(), "\\uffffffff");
+    }
+
+    #[test]
+    fn decodes_every_encoded_key() {
+        for (name, typ) in [
+            ("MasterVolume", "float"),
+            ("Fullscreen", "bool"),
+            ("Mouse_Sensitivity", "int"),
+            ("PlayerName", "str"),
+            ("Jump_key", "key"),
+            ("Look_axis", "axis"),
+            ("HitMarkerColor_r", "color"),
+            ("Screenmanager Resolution Width", "int"),
+        ] {
+            let regedit_name = player_prefs_name(&encode_player_prefs_key(name, typ));
+            assert_eq!(decode_regedit_name(&regedit_name), (name.to_string(), typ));
+        }
+    }
+
+    #[test]
+    fn untagged_strings_decode_to_their_key() {
+        assert_eq!(
+            decode_regedit_name("unity.cloud_userid_h2665564582"),
+            ("unity.cloud_userid".to_string(), "str")
+        );
+        assert_eq!(encode_player_prefs_key("unity.cloud_userid", "str"), "unity.cloud_userid_str");
+    }
+
+    #[test]
+    fn hashes_like_unity() {
+        for regedit_name in [
+            "Screenmanager Resolution Width_h182942802",
+            "Screenmanager Resolution Height_h2627697771",
+            "Screenmanager Fullscreen mode_h3630240806",
+            "UnityGraphicsQuality_h1669003810",
+            "unity.cloud_userid_h2665564582",
+        ] {
+            let (name, _) = regedit_name.rsplit_once("_h").unwrap();
+            assert_eq!(player_prefs_name(name), regedit_name);
+        }
+    }
+}
@@ -0,0 +1,566 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+    path::Path,
+};
+
+use crate::{
+    battlebit::{BattlebitType, Key},
+    keybinds, keycode,
+};
+
+/// The formats a cheat sheet can be drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Svg,
+    /// A standalone page with the SVG inline and a table of every binding.
+    Html,
+}
+
+impl Format {
+    /// The format a file should be written in, going by its extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "svg" => Some(Format::Svg),
+            "html" | "htm" => Some(Format::Html),
+            _ => None,
+        }
+    }
+
+    /// Draws the `Key` and `Axis` entries of `settings` in this format.
+    pub fn render<'a>(&self, settings: impl IntoIterator<Item = (&'a str, &'a BattlebitType)>) -> String {
+        let sheet = CheatSheet::new(settings);
+        match self {
+            Format::Svg => sheet.to_svg(),
+            Format::Html => sheet.to_html(),
+        }
+    }
+}
+
+/// The size of a 1-wide key, in pixels.
+const UNIT: f64 = 56.0;
+const GAP: f64 = 4.0;
+const MARGIN: f64 = 16.0;
+/// Where the keyboard starts, below the title.
+const TOP: f64 = 48.0;
+const LINE_HEIGHT: f64 = 18.0;
+
+const BOUND: &str = "#ffffff";
+const UNBOUND: &str = "#e4e4e4";
+const CONFLICT: &str = "#ffb3b3";
+
+/// A key or mouse button on the diagram, positioned in key widths.
+struct Cap {
+    code: i32,
+    label: &'static str,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+/// Lays keys out left to right from `x`, each `(name, label, width)`.
+fn row(caps: &mut Vec<Cap>, mut x: f64, y: f64, keys: &[(&str, &'static str, f64)]) {
+    for &(name, label, w) in keys {
+        caps.push(Cap {
+            code: keycode::from_name(name).expect("The diagram only uses known keys"),
+            label,
+            x,
+            y,
+            w,
+            h: 1.0,
+        });
+        x += w;
+    }
+}
+
+/// Lays out one key per ASCII character, labeled with the character itself.
+fn letters(caps: &mut Vec<Cap>, x: f64, y: f64, keys: &'static str) {
+    for (i, c) in keys.char_indices() {
+        caps.push(Cap {
+            code: c.to_ascii_lowercase() as i32,
+            label: &keys[i..i + 1],
+            x: x + i as f64,
+            y,
+            w: 1.0,
+            h: 1.0,
+        });
+    }
+}
+
+const FUNCTION_KEYS: [&str; 12] = ["F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12"];
+
+/// A full-size keyboard with a mouse to its right.
+fn layout() -> Vec<Cap> {
+    let mut caps = Vec::new();
+
+    row(&mut caps, 0.0, 0.0, &[("Escape", "Esc", 1.0)]);
+    for (i, name) in FUNCTION_KEYS.iter().enumerate() {
+        // In groups of four, with a gap between each.
+        let x = [2.0, 6.5, 11.0][i / 4] + (i % 4) as f64;
+        row(&mut caps, x, 0.0, &[(name, name, 1.0)]);
+    }
+    row(&mut caps, 15.25, 0.0, &[("Print", "PrtSc", 1.0), ("ScrollLock", "ScrLk", 1.0), ("Pause", "Pause", 1.0)]);
+
+    row(&mut caps, 0.0, 1.5, &[("BackQuote", "`", 1.0)]);
+    row(
+        &mut caps,
+        1.0,
+        1.5,
+        &[
+            ("Alpha1", "1", 1.0),
+            ("Alpha2", "2", 1.0),
+            ("Alpha3", "3", 1.0),
+            ("Alpha4", "4", 1.0),
+            ("Alpha5", "5", 1.0),
+            ("Alpha6", "6", 1.0),
+            ("Alpha7", "7", 1.0),
+            ("Alpha8", "8", 1.0),
+            ("Alpha9", "9", 1.0),
+            ("Alpha0", "0", 1.0),
+            ("Minus", "-", 1.0),
+            ("Equals", "=", 1.0),
+            ("Backspace", "Backspace", 2.0),
+        ],
+    );
+    row(&mut caps, 0.0, 2.5, &[("Tab", "Tab", 1.5)]);
+    letters(&mut caps, 1.5, 2.5, "QWERTYUIOP");
+    row(&mut caps, 11.5, 2.5, &[("LeftBracket", "[", 1.0), ("RightBracket", "]", 1.0), ("Backslash", "\\", 1.5)]);
+    row(&mut caps, 0.0, 3.5, &[("CapsLock", "Caps", 1.75)]);
+    letters(&mut caps, 1.75, 3.5, "ASDFGHJKL");
+    row(&mut caps, 10.75, 3.5, &[("Semicolon", ";", 1.0), ("Quote", "'", 1.0), ("Return", "Enter", 2.25)]);
+    row(&mut caps, 0.0, 4.5, &[("LeftShift", "Shift", 2.25)]);
+    letters(&mut caps, 2.25, 4.5, "ZXCVBNM");
+    row(
+        &mut caps,
+        9.25,
+        4.5,
+        &[("Comma", ",", 1.0), ("Period", ".", 1.0), ("Slash", "/", 1.0), ("RightShift", "Shift", 2.75)],
+    );
+    row(
+        &mut caps,
+        0.0,
+        5.5,
+        &[
+            ("LeftControl", "Ctrl", 1.25),
+            ("LeftWindows", "Win", 1.25),
+            ("LeftAlt", "Alt", 1.25),
+            ("Space", "Space", 6.25),
+            ("RightAlt", "Alt", 1.25),
+            ("RightWindows", "Win", 1.25),
+            ("Menu", "Menu", 1.25),
+            ("RightControl", "Ctrl", 1.25),
+        ],
+    );
+
+    row(&mut caps, 15.25, 1.5, &[("Insert", "Ins", 1.0), ("Home", "Home", 1.0), ("PageUp", "PgUp", 1.0)]);
+    row(&mut caps, 15.25, 2.5, &[("Delete", "Del", 1.0), ("End", "End", 1.0), ("PageDown", "PgDn", 1.0)]);
+    row(&mut caps, 16.25, 4.5, &[("UpArrow", "↑", 1.0)]);
+    row(&mut caps, 15.25, 5.5, &[("LeftArrow", "←", 1.0), ("DownArrow", "↓", 1.0), ("RightArrow", "→", 1.0)]);
+
+    row(
+        &mut caps,
+        18.5,
+        1.5,
+        &[("Numlock", "Num", 1.0), ("KeypadDivide", "/", 1.0), ("KeypadMultiply", "*", 1.0), ("KeypadMinus", "-", 1.0)],
+    );
+    row(&mut caps, 18.5, 2.5, &[("Keypad7", "7", 1.0), ("Keypad8", "8", 1.0), ("Keypad9", "9", 1.0)]);
+    row(&mut caps, 18.5, 3.5, &[("Keypad4", "4", 1.0), ("Keypad5", "5", 1.0), ("Keypad6", "6", 1.0)]);
+    row(&mut caps, 18.5, 4.5, &[("Keypad1", "1", 1.0), ("Keypad2", "2", 1.0), ("Keypad3", "3", 1.0)]);
+    row(&mut caps, 18.5, 5.5, &[("Keypad0", "0", 2.0), ("KeypadPeriod", ".", 1.0)]);
+    // Both two rows tall.
+    row(&mut caps, 21.5, 2.5, &[("KeypadPlus", "+", 1.0)]);
+    caps.last_mut().unwrap().h = 2.0;
+    row(&mut caps, 21.5, 4.5, &[("KeypadEnter", "Enter", 1.0)]);
+    caps.last_mut().unwrap().h = 2.0;
+
+    let mouse = [
+        ("Mouse0", "Left", 23.75, 1.5, 1.5, 2.0),
+        ("Mouse1", "Right", 25.25, 1.5, 1.5, 2.0),
+        ("Mouse2", "Middle", 24.5, 3.5, 1.5, 1.0),
+        ("Mouse3", "Mouse3", 23.75, 4.5, 1.5, 1.0),
+        ("Mouse4", "Mouse4", 25.25, 4.5, 1.5, 1.0),
+        ("Mouse5", "Mouse5", 23.75, 5.5, 1.5, 1.0),
+        ("Mouse6", "Mouse6", 25.25, 5.5, 1.5, 1.0),
+    ];
+    caps.extend(mouse.iter().map(|&(name, label, x, y, w, h)| Cap {
+        code: keycode::from_name(name).unwrap(),
+        label,
+        x,
+        y,
+        w,
+        h,
+    }));
+
+    caps
+}
+
+/// How wide the diagram is, in key widths, and where the mouse's outline goes.
+const WIDTH: f64 = 27.0;
+const MOUSE: (f64, f64, f64, f64) = (23.5, 1.25, 3.5, 5.75);
+const HEIGHT: f64 = 7.0;
+
+/// A setting's name the way it reads on a key, e.g. `Jump_key` as `Jump`.
+fn action(name: &str) -> &str {
+    name.strip_suffix("_key")
+        .or_else(|| name.strip_suffix("_axis"))
+        .unwrap_or(name)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Splits `text` into lines of at most `width` characters, breaking between words and before capitals.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() || c == '_' {
+            words.push(std::mem::take(&mut word));
+            continue;
+        }
+        if c == ',' {
+            word.push(c);
+            words.push(std::mem::take(&mut word));
+            continue;
+        }
+        if c.is_uppercase() && word.chars().last().is_some_and(|last| last.is_lowercase()) {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+    }
+    words.push(word);
+
+    let mut lines: Vec<String> = Vec::new();
+    for word in words.into_iter().filter(|w| !w.is_empty()) {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= width => {
+                line.push(' ');
+                line.push_str(&word);
+            }
+            _ => lines.push(word),
+        }
+    }
+
+    lines
+}
+
+/// Everything a cheat sheet shows, gathered from the settings.
+struct CheatSheet<'a> {
+    /// The actions bound to each key, sorted by name.
+    bindings: BTreeMap<i32, Vec<&'a str>>,
+    conflicts: Vec<keybinds::Conflict>,
+    /// Actions bound to `None`, sorted by name.
+    unbound: Vec<&'a str>,
+    axes: Vec<(&'a str, &'a BattlebitType)>,
+}
+
+impl<'a> CheatSheet<'a> {
+    fn new(settings: impl IntoIterator<Item = (&'a str, &'a BattlebitType)>) -> Self {
+        let mut keys = Vec::new();
+        let mut axes = Vec::new();
+
+        for (name, value) in settings {
+            match value {
+                BattlebitType::Key(_) => keys.push((name, value)),
+                BattlebitType::Axis(_) => axes.push((name, value)),
+                _ => {}
+            }
+        }
+        axes.sort_by_key(|(name, _)| *name);
+
+        let conflicts = keybinds::conflicts(keys.iter().copied());
+        let mut bindings: BTreeMap<i32, Vec<&str>> = BTreeMap::new();
+        let mut unbound = Vec::new();
+        for (name, value) in keys {
+            match value {
+                BattlebitType::Key(Key(0)) => unbound.push(name),
+                BattlebitType::Key(Key(key)) => bindings.entry(*key).or_default().push(name),
+                _ => {}
+            }
+        }
+        bindings.values_mut().for_each(|names| names.sort());
+        unbound.sort();
+
+        CheatSheet {
+            bindings,
+            conflicts,
+            unbound,
+            axes,
+        }
+    }
+
+    fn conflicting(&self) -> HashSet<i32> {
+        self.conflicts.iter().map(|conflict| conflict.key).collect()
+    }
+
+    /// The lines of the legend under the diagram.
+    fn legend(&self, caps: &[Cap]) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        let drawn: HashSet<i32> = caps.iter().map(|cap| cap.code).collect();
+        let hidden: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(key, _)| !drawn.contains(key))
+            .flat_map(|(key, names)| names.iter().map(move |name| format!("{}: {}", action(name), Key(*key).name())))
+            .collect();
+
+        if !self.conflicts.is_empty() {
+            lines.push("Conflicts".to_string());
+            lines.extend(self.conflicts.iter().map(|conflict| format!("  {}", conflict)));
+        }
+        if !hidden.is_empty() {
+            lines.push("Not on the diagram".to_string());
+            lines.extend(hidden.into_iter().map(|line| format!("  {}", line)));
+        }
+        if !self.unbound.is_empty() {
+            lines.push("Not bound".to_string());
+            lines.extend(self.unbound.iter().map(|name| format!("  {}", action(name))));
+        }
+        if !self.axes.is_empty() {
+            lines.push("Axes".to_string());
+            lines.extend(
+                self.axes
+                    .iter()
+                    .map(|(name, value)| format!("  {}: {}", action(name), value.to_display_string())),
+            );
+        }
+
+        lines
+    }
+
+    fn to_svg(&self) -> String {
+        let caps = layout();
+        let conflicting = self.conflicting();
+        let legend = self.legend(&caps);
+
+        let width = WIDTH * UNIT + 2.0 * MARGIN;
+        let legend_top = TOP + HEIGHT * UNIT + LINE_HEIGHT;
+        let height = legend_top + (legend.len() as f64 + 1.0) * LINE_HEIGHT + MARGIN;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">"#,
+            w = width,
+            h = height
+        );
+        let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#fafafa"/>"##);
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="30" font-size="20" font-weight="bold">BattleBit Remastered keybindings</text>"#,
+            MARGIN
+        );
+
+        let (mx, my, mw, mh) = MOUSE;
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" rx="{}" fill="none" stroke="#999" stroke-width="2"/>"##,
+            MARGIN + mx * UNIT,
+            TOP + my * UNIT,
+            mw * UNIT,
+            mh * UNIT,
+            (UNIT * 0.8).round()
+        );
+
+        for cap in &caps {
+            let x = MARGIN + cap.x * UNIT;
+            let y = TOP + cap.y * UNIT;
+            let (w, h) = (cap.w * UNIT - GAP, cap.h * UNIT - GAP);
+            let names = self.bindings.get(&cap.code);
+            let fill = match names {
+                _ if conflicting.contains(&cap.code) => CONFLICT,
+                Some(_) => BOUND,
+                None => UNBOUND,
+            };
+            let opacity = if names.is_some() { 1.0 } else { 0.6 };
+
+            let _ = writeln!(svg, r#"<g opacity="{}">"#, opacity);
+            if let Some(names) = names {
+                let _ = writeln!(svg, "<title>{}</title>", escape(&names.join(", ")));
+            }
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" rx="6" fill="{}" stroke="#888"/>"##,
+                x, y, w, h, fill
+            );
+            let _ = writeln!(
+                svg,
+                r##"<text x="{}" y="{}" font-size="11" font-weight="bold" fill="#555">{}</text>"##,
+                x + 5.0,
+                y + 14.0,
+                escape(cap.label)
+            );
+
+            if let Some(names) = names {
+                let text: Vec<&str> = names.iter().map(|name| action(name)).collect();
+                let max_lines = ((h - 20.0) / 10.0) as usize;
+                let mut lines = wrap(&text.join(", "), ((w - 8.0) / 5.2) as usize);
+                if lines.len() > max_lines {
+                    lines.truncate(max_lines);
+                    if let Some(last) = lines.last_mut() {
+                        last.push('…');
+                    }
+                }
+                for (i, line) in lines.iter().enumerate() {
+                    let _ = writeln!(
+                        svg,
+                        r#"<text x="{}" y="{}" font-size="9">{}</text>"#,
+                        x + 5.0,
+                        y + 26.0 + i as f64 * 10.0,
+                        escape(line)
+                    );
+                }
+            }
+            let _ = writeln!(svg, "</g>");
+        }
+
+        let swatches = [(BOUND, "Bound"), (UNBOUND, "Unbound"), (CONFLICT, "Conflict")];
+        for (i, (fill, label)) in swatches.iter().enumerate() {
+            let x = MARGIN + i as f64 * 110.0;
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{}" y="{}" width="14" height="14" rx="3" fill="{}" stroke="#888"/><text x="{}" y="{}" font-size="12">{}</text>"##,
+                x,
+                legend_top - 11.0,
+                fill,
+                x + 20.0,
+                legend_top,
+                label
+            );
+        }
+        for (i, line) in legend.iter().enumerate() {
+            let indented = line.starts_with(' ');
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" font-size="12"{}>{}</text>"#,
+                MARGIN + if indented { 16.0 } else { 0.0 },
+                legend_top + (i + 1) as f64 * LINE_HEIGHT,
+                if indented { "" } else { r#" font-weight="bold""# },
+                escape(line.trim_start())
+            );
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn to_html(&self) -> String {
+        let conflicting: HashSet<&str> = self
+            .conflicts
+            .iter()
+            .flat_map(|conflict| conflict.settings.iter().map(String::as_str))
+            .collect();
+
+        let mut rows = String::new();
+        let mut bindings: Vec<(&str, i32)> = self
+            .bindings
+            .iter()
+            .flat_map(|(key, names)| names.iter().map(move |name| (*name, *key)))
+            .collect();
+        bindings.sort();
+        for (name, key) in bindings {
+            let _ = writeln!(
+                rows,
+                r#"<tr{}><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+                if conflicting.contains(name) { r#" class="conflict""# } else { "" },
+                escape(action(name)),
+                escape(&Key(key).name()),
+                keybinds::context(name)
+            );
+        }
+
+        format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>BattleBit Remastered keybindings</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+svg {{ max-width: 100%; height: auto; }}
+table {{ border-collapse: collapse; margin-top: 2em; }}
+th, td {{ border: 1px solid #ccc; padding: 0.25em 0.75em; text-align: left; }}
+tr.conflict {{ background: {conflict}; }}
+@media print {{ body {{ margin: 0; }} }}
+</style>
+</head>
+<body>
+{svg}<table>
+<tr><th>Action</th><th>Key</th><th>Context</th></tr>
+{rows}</table>
+</body>
+</html>
+"#,
+            conflict = CONFLICT,
+            svg = self.to_svg(),
+            rows = rows
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battlebit::{Axis, Float};
+
+    #[test]
+    fn picks_the_format_by_extension() {
+        assert_eq!(Format::from_path("sheet.svg"), Some(Format::Svg));
+        assert_eq!(Format::from_path("Sheet.HTM"), Some(Format::Html));
+        assert_eq!(Format::from_path("sheet.toml"), None);
+        assert_eq!(Format::from_path("sheet"), None);
+    }
+
+    #[test]
+    fn wraps_between_words_and_capitals() {
+        assert_eq!(wrap("HeliThrottle_Up", 20), ["Heli Throttle Up"]);
+        assert_eq!(wrap("Jump, Crouch", 6), ["Jump,", "Crouch"]);
+        assert_eq!(wrap("HUDMenu", 4), ["HUDMenu"]);
+        assert!(wrap("", 4).is_empty());
+    }
+
+    #[test]
+    fn lists_what_the_diagram_cant_show() {
+        let settings = [
+            ("Jump_key", BattlebitType::Key(Key(32))),
+            ("Crouch_key", BattlebitType::Key(Key(32))),
+            ("Fire_key", BattlebitType::Key(Key(0))),
+            ("Ping_key", BattlebitType::Key(Key(1000))),
+            ("Look_axis", BattlebitType::Axis(Axis(1))),
+            ("MasterVolume", BattlebitType::Float(Float(0.5))),
+        ];
+        let sheet = CheatSheet::new(settings.iter().map(|(name, value)| (*name, value)));
+
+        assert_eq!(sheet.bindings[&32], ["Crouch_key", "Jump_key"]);
+        assert_eq!(sheet.conflicting(), HashSet::from([32]));
+        assert_eq!(
+            sheet.legend(&layout()),
+            [
+                "Conflicts",
+                "  Space (infantry): `Crouch_key`, `Jump_key`",
+                "Not on the diagram",
+                "  Ping: \\u03e8",
+                "Not bound",
+                "  Fire",
+                "Axes",
+                "  Look: 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_names_in_the_markup() {
+        let settings = [("<b>&_key", BattlebitType::Key(Key(32)))];
+        let html = Format::Html.render(settings.iter().map(|(name, value)| (*name, value)));
+
+        assert!(html.contains("<td>&lt;b&gt;&amp;</td>"));
+        assert!(!html.contains("<b>"));
+    }
+}
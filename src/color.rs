@@ -0,0 +1,241 @@
+use crate::{
+    battlebit::{BattlebitType, Color, State},
+    error::SettingsError,
+};
+
+/// The channel suffixes Unity stores a color's components under, in `Color` channel order.
+pub const CHANNELS: [char; 4] = ['r', 'g', 'b', 'a'];
+
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("black", 0x000000),
+    ("white", 0xFFFFFF),
+    ("red", 0xFF0000),
+    ("lime", 0x00FF00),
+    ("green", 0x008000),
+    ("blue", 0x0000FF),
+    ("yellow", 0xFFFF00),
+    ("cyan", 0x00FFFF),
+    ("aqua", 0x00FFFF),
+    ("magenta", 0xFF00FF),
+    ("fuchsia", 0xFF00FF),
+    ("orange", 0xFFA500),
+    ("purple", 0x800080),
+    ("pink", 0xFFC0CB),
+    ("hotpink", 0xFF69B4),
+    ("gray", 0x808080),
+    ("grey", 0x808080),
+    ("silver", 0xC0C0C0),
+    ("maroon", 0x800000),
+    ("olive", 0x808000),
+    ("navy", 0x000080),
+    ("teal", 0x008080),
+    ("gold", 0xFFD700),
+    ("brown", 0xA52A2A),
+    ("violet", 0xEE82EE),
+    ("indigo", 0x4B0082),
+    ("crimson", 0xDC143C),
+    ("coral", 0xFF7F50),
+    ("turquoise", 0x40E0D0),
+    ("chartreuse", 0x7FFF00),
+    ("springgreen", 0x00FF7F),
+    ("deepskyblue", 0x00BFFF),
+];
+
+/// Parses a color as `[r, g, b, a]`, each from 0 to 1.
+///
+/// Accepts `#RGB`, `#RGBA`, `#RRGGBB` and `#RRGGBBAA` hex, `rgb()`/`rgba()` with 0-255 or percentage channels,
+/// `hsl()`/`hsla()`, `transparent` and the common CSS color names.
+pub fn parse_color(input: &str) -> Option<[f64; 4]> {
+    let input = input.trim().to_lowercase();
+
+    if let Some(hex) = input.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+
+    if let Some((function, args)) = input.strip_suffix(')').and_then(|s| s.split_once('(')) {
+        let args: Vec<&str> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|a| !a.is_empty())
+            .collect();
+
+        return match function.trim() {
+            "rgb" | "rgba" => parse_rgb(&args),
+            "hsl" | "hsla" => parse_hsl(&args),
+            _ => None,
+        };
+    }
+
+    if input == "transparent" {
+        return Some([0.0, 0.0, 0.0, 0.0]);
+    }
+
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| *name == input)
+        .map(|&(_, rgb)| {
+            let [_, r, g, b] = rgb.to_be_bytes();
+            [r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0, 1.0]
+        })
+}
+
+/// Formats a color as `#RRGGBBAA` hex, rounding each channel to 8 bits.
+pub fn to_hex(color: [f64; 4]) -> String {
+    color
+        .iter()
+        .fold(String::from("#"), |hex, c| hex + &format!("{:02X}", (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
+
+/// The color a channel setting such as `HitMarkerColor_r` belongs to.
+pub fn color_name(channel: &str) -> &str {
+    channel.get(..channel.len().saturating_sub(2)).unwrap_or(channel)
+}
+
+impl State {
+    /// The channels of color `name`, if it is one. Channels missing from the registry are taken as 1.
+    pub fn color(&self, name: &str) -> Option<[f64; 4]> {
+        let mut rgba = [1.0; 4];
+        let mut found = false;
+
+        for (i, c) in CHANNELS.iter().enumerate() {
+            if let Some(BattlebitType::Color(channel)) = self.registry.get(&format!("{}_{}", name, c)) {
+                rgba[i] = channel.1;
+                found = true;
+            }
+        }
+
+        found.then_some(rgba)
+    }
+
+    /// Queues every channel of the color `name` to be written with `rgba`.
+    pub fn update_color(&mut self, name: &str, rgba: [f64; 4]) -> Result<(), SettingsError> {
+        for (i, c) in CHANNELS.iter().enumerate() {
+            let channel = BattlebitType::Color(Color(i as i32, rgba[i]));
+            self.update_registry(&format!("{}_{}", name, c), channel)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<[f64; 4]> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let digits: Vec<u8> = hex.bytes().map(|b| (b as char).to_digit(16).unwrap() as u8).collect();
+    let channels: Vec<u8> = match digits.len() {
+        // Short forms repeat each digit, so `#F30` is `#FF3300`.
+        3 | 4 => digits.iter().map(|d| d * 17).collect(),
+        6 | 8 => digits.chunks(2).map(|d| d[0] * 16 + d[1]).collect(),
+        _ => return None,
+    };
+
+    let channel = |i: usize| channels.get(i).map_or(1.0, |&c| c as f64 / 255.0);
+    Some([channel(0), channel(1), channel(2), channel(3)])
+}
+
+/// A number, or a percentage of `max`, scaled to 0-1.
+fn parse_component(arg: &str, max: f64) -> Option<f64> {
+    let value = match arg.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().ok()? / 100.0,
+        None => arg.parse::<f64>().ok()? / max,
+    };
+
+    value.is_finite().then(|| value.clamp(0.0, 1.0))
+}
+
+fn parse_alpha(args: &[&str]) -> Option<f64> {
+    match args {
+        [_, _, _] => Some(1.0),
+        [_, _, _, alpha] => parse_component(alpha, 1.0),
+        _ => None,
+    }
+}
+
+fn parse_rgb(args: &[&str]) -> Option<[f64; 4]> {
+    let alpha = parse_alpha(args)?;
+    Some([
+        parse_component(args[0], 255.0)?,
+        parse_component(args[1], 255.0)?,
+        parse_component(args[2], 255.0)?,
+        alpha,
+    ])
+}
+
+fn parse_hsl(args: &[&str]) -> Option<[f64; 4]> {
+    let alpha = parse_alpha(args)?;
+
+    let hue = args[0].strip_suffix("deg").unwrap_or(args[0]).parse::<f64>().ok()?;
+    if !hue.is_finite() {
+        return None;
+    }
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let saturation = parse_component(args[1], 100.0)?;
+    let lightness = parse_component(args[2], 100.0)?;
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+
+    Some([r + m, g + m, b + m, alpha])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(input: &str) -> Option<String> {
+        parse_color(input).map(to_hex)
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(hex("#F30"), Some("#FF3300FF".to_string()));
+        assert_eq!(hex("#f30c"), Some("#FF3300CC".to_string()));
+        assert_eq!(hex("#FF3300"), Some("#FF3300FF".to_string()));
+        assert_eq!(hex(" #ff3300cc "), Some("#FF3300CC".to_string()));
+        assert_eq!(hex("#FF330"), None);
+        assert_eq!(hex("#GG3300"), None);
+    }
+
+    #[test]
+    fn parses_functions() {
+        assert_eq!(hex("rgb(255, 51, 0)"), Some("#FF3300FF".to_string()));
+        assert_eq!(hex("rgba(255 51 0 / 0.8)"), Some("#FF3300CC".to_string()));
+        assert_eq!(hex("rgb(100%, 20%, 0%)"), Some("#FF3300FF".to_string()));
+        assert_eq!(hex("hsl(12, 100%, 50%)"), Some("#FF3300FF".to_string()));
+        assert_eq!(hex("hsla(120deg, 100%, 25%, 0.5)"), Some("#00800080".to_string()));
+        assert_eq!(hex("rgb(255, 51)"), None);
+        assert_eq!(hex("cmyk(0, 0, 0, 0)"), None);
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!(hex("orange"), Some("#FFA500FF".to_string()));
+        assert_eq!(hex("Red"), Some("#FF0000FF".to_string()));
+        assert_eq!(hex("transparent"), Some("#00000000".to_string()));
+        assert_eq!(hex("reddish"), None);
+    }
+
+    #[test]
+    fn clamps_out_of_range_channels() {
+        assert_eq!(parse_color("rgb(300, -5, 0)"), Some([1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(parse_color("rgba(0, 0, 0, 2)"), Some([0.0, 0.0, 0.0, 1.0]));
+        assert_eq!(parse_color("hsl(-120, 150%, 50%)"), parse_color("hsl(240, 100%, 50%)"));
+        assert_eq!(parse_color("rgb(nan, 0, 0)"), None);
+        assert_eq!(to_hex([1.5, -0.5, 0.2, 1.0]), "#FF0033FF");
+    }
+
+    #[test]
+    fn channels_belong_to_their_color() {
+        assert_eq!(color_name("HitMarkerColor_r"), "HitMarkerColor");
+    }
+}
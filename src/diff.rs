@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use ansi_term::Colour;
+use serde::Serialize;
+
+use crate::{
+    battlebit::{BattlebitType, Color},
+    color,
+};
+
+/// A setting as it is compared. Colors are compared whole, rather than channel by channel.
+#[derive(Debug, PartialEq)]
+pub enum Setting {
+    Value(BattlebitType),
+    /// `[r, g, b, a]`, each from 0 to 1.
+    Color([f64; 4]),
+}
+
+impl Setting {
+    fn to_display_string(&self) -> String {
+        match self {
+            Setting::Value(value) => value.to_display_string(),
+            Setting::Color(rgba) => color::to_hex(*rgba),
+        }
+    }
+
+    fn to_reg_type_str(&self) -> &'static str {
+        match self {
+            Setting::Value(value) => value.to_reg_type_str(),
+            Setting::Color(_) => "color",
+        }
+    }
+
+    fn delta(&self, old: &Setting) -> Option<f64> {
+        match (old, self) {
+            (Setting::Value(old), Setting::Value(new)) => new.delta(old),
+            _ => None,
+        }
+    }
+}
+
+/// How a single setting differs between two sets of settings.
+pub enum Difference {
+    Added(Setting),
+    Removed(Setting),
+    Changed(Setting, Setting),
+}
+
+/// Merges the channels of each color into one setting. Channels that are missing are taken as 1.
+fn merge_colors(settings: HashMap<String, BattlebitType>) -> HashMap<String, Setting> {
+    let mut merged = HashMap::new();
+
+    for (name, value) in settings {
+        match value {
+            BattlebitType::Color(Color(channel, c)) => {
+                let setting = merged
+                    .entry(color::color_name(&name).to_string())
+                    .or_insert(Setting::Color([1.0; 4]));
+                if let Setting::Color(rgba) = setting {
+                    if let Some(slot) = rgba.get_mut(channel as usize) {
+                        *slot = c;
+                    }
+                }
+            }
+            value => {
+                merged.insert(name, Setting::Value(value));
+            }
+        }
+    }
+
+    merged
+}
+
+/// Compares two sets of settings, returning what it takes to get from `old` to `new`, sorted by name.
+pub fn diff(
+    old: HashMap<String, BattlebitType>,
+    new: HashMap<String, BattlebitType>,
+) -> Vec<(String, Difference)> {
+    let (mut old, new) = (merge_colors(old), merge_colors(new));
+    let mut differences = Vec::new();
+
+    for (name, new) in new {
+        match old.remove(&name) {
+            Some(old) if old == new => {}
+            Some(old) => differences.push((name, Difference::Changed(old, new))),
+            None => differences.push((name, Difference::Added(new))),
+        }
+    }
+
+    for (name, old) in old {
+        differences.push((name, Difference::Removed(old)));
+    }
+
+    differences.sort_by(|a, b| a.0.cmp(&b.0));
+    differences
+}
+
+pub fn print(differences: &[(String, Difference)]) {
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+
+    for (name, difference) in differences {
+        match difference {
+            Difference::Added(new) => {
+                added += 1;
+                let line = format!("+ {} = {} ({})", name, new.to_display_string(), new.to_reg_type_str());
+                println!("{}", Colour::Green.paint(line));
+            }
+            Difference::Removed(old) => {
+                removed += 1;
+                let line = format!("- {} = {} ({})", name, old.to_display_string(), old.to_reg_type_str());
+                println!("{}", Colour::Red.paint(line));
+            }
+            Difference::Changed(old, new) => {
+                changed += 1;
+                // Rounded so float noise like 0.19999999999999998 doesn't clutter the output.
+                let delta = match new.delta(old) {
+                    Some(delta) => format!(" ({:+})", (delta * 1e6).round() / 1e6),
+                    None => String::new(),
+                };
+                let line = format!(
+                    "~ {}: {} -> {}{}",
+                    name,
+                    old.to_display_string(),
+                    new.to_display_string(),
+                    delta
+                );
+                println!("{}", Colour::Yellow.paint(line));
+            }
+        }
+    }
+
+    println!("{} added, {} removed, {} changed", added, removed, changed);
+}
+
+#[derive(Serialize)]
+struct JsonDifference {
+    name: String,
+    change: &'static str,
+    #[serde(rename = "type")]
+    typ: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delta: Option<f64>,
+}
+
+fn json_value(value: &Setting) -> Result<serde_json::Value, serde_json::Error> {
+    match value {
+        Setting::Value(BattlebitType::Key(k)) => Ok(serde_json::Value::String(k.name())),
+        Setting::Value(value) => serde_json::to_value(value.to_toml_value()),
+        Setting::Color(rgba) => Ok(serde_json::Value::String(color::to_hex(*rgba))),
+    }
+}
+
+/// The differences as a JSON array, one object per setting, for scripts.
+pub fn to_json(differences: &[(String, Difference)]) -> Result<String, serde_json::Error> {
+    let differences = differences
+        .iter()
+        .map(|(name, difference)| {
+            let (change, typ, old, new, delta) = match difference {
+                Difference::Added(new) => ("added", new.to_reg_type_str(), None, Some(new), None),
+                Difference::Removed(old) => ("removed", old.to_reg_type_str(), Some(old), None, None),
+                Difference::Changed(old, new) => {
+                    ("changed", new.to_reg_type_str(), Some(old), Some(new), new.delta(old))
+                }
+            };
+
+            Ok(JsonDifference {
+                name: name.clone(),
+                change,
+                typ: typ.to_string(),
+                old: old.map(json_value).transpose()?,
+                new: new.map(json_value).transpose()?,
+                delta,
+            })
+        })
+        .collect::<Result<Vec<_>, serde_json::Error>>()?;
+
+    serde_json::to_string_pretty(&differences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battlebit::{Float, Int, Key};
+
+    fn color(name: &str, rgba: [f64; 4]) -> Vec<(String, BattlebitType)> {
+        color::CHANNELS
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (format!("{}_{}", name, c), BattlebitType::Color(Color(i as i32, rgba[i]))))
+            .collect()
+    }
+
+    fn settings(values: Vec<(&str, BattlebitType)>) -> HashMap<String, BattlebitType> {
+        values.into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+    }
+
+    #[test]
+    fn finds_added_removed_and_changed_settings() {
+        let old = settings(vec![
+            ("Jump_key", BattlebitType::Key(Key(32))),
+            ("MasterVolume", BattlebitType::Float(Float(0.5))),
+            ("Sensitivity", BattlebitType::Int(Int(7))),
+        ]);
+        let new = settings(vec![
+            ("Crouch_key", BattlebitType::Key(Key(99))),
+            ("MasterVolume", BattlebitType::Float(Float(0.25))),
+            ("Sensitivity", BattlebitType::Int(Int(7))),
+        ]);
+
+        let differences = diff(old, new);
+        let names: Vec<&str> = differences.iter().map(|(name, _)| name.as_str()).collect();
+
+        assert_eq!(names, ["Crouch_key", "Jump_key", "MasterVolume"]);
+        assert!(matches!(differences[0].1, Difference::Added(Setting::Value(BattlebitType::Key(Key(99))))));
+        assert!(matches!(differences[1].1, Difference::Removed(Setting::Value(BattlebitType::Key(Key(32))))));
+        assert!(matches!(
+            differences[2].1,
+            Difference::Changed(
+                Setting::Value(BattlebitType::Float(Float(a))),
+                Setting::Value(BattlebitType::Float(Float(b)))
+            ) if a == 0.5 && b == 0.25
+        ));
+    }
+
+    #[test]
+    fn json_has_one_object_per_setting() {
+        let old = settings(vec![
+            ("Jump_key", BattlebitType::Key(Key(32))),
+            ("Sensitivity", BattlebitType::Int(Int(7))),
+        ]);
+        let new = settings(vec![
+            ("Crouch_key", BattlebitType::Key(Key(99))),
+            ("Sensitivity", BattlebitType::Int(Int(10))),
+        ]);
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&diff(old, new)).unwrap()).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!([
+                { "name": "Crouch_key", "change": "added", "type": "key", "new": "C" },
+                { "name": "Jump_key", "change": "removed", "type": "key", "old": "Space" },
+                { "name": "Sensitivity", "change": "changed", "type": "int", "old": 7, "new": 10, "delta": 3.0 },
+            ])
+        );
+    }
+
+    #[test]
+    fn colors_are_compared_whole() {
+        let old: HashMap<_, _> = color("HitMarkerColor", [1.0, 0.0, 0.0, 1.0]).into_iter().collect();
+        let new: HashMap<_, _> = color("HitMarkerColor", [1.0, 0.2, 0.0, 1.0]).into_iter().collect();
+
+        let differences = diff(old, new);
+
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].0, "HitMarkerColor");
+        assert!(matches!(
+            &differences[0].1,
+            Difference::Changed(Setting::Color(a), Setting::Color(b)) if a == &[1.0, 0.0, 0.0, 1.0] && b == &[1.0, 0.2, 0.0, 1.0]
+        ));
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&differences).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                { "name": "HitMarkerColor", "change": "changed", "type": "color", "old": "#FF0000FF", "new": "#FF3300FF" },
+            ])
+        );
+    }
+}
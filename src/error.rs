@@ -0,0 +1,141 @@
+use std::{fmt, path::PathBuf};
+
+/// Where in a file a problem was found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    pub file: Option<PathBuf>,
+    /// 1-based, when the format has lines to point at.
+    pub line: Option<usize>,
+}
+
+impl Location {
+    /// The location of byte `offset` in `text`.
+    pub fn at_offset(text: &str, offset: usize) -> Self {
+        let offset = offset.min(text.len());
+        Location {
+            file: None,
+            line: Some(text.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count() + 1),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}", file.display(), line),
+            (Some(file), None) => write!(f, "{}", file.display()),
+            (None, Some(line)) => write!(f, "line {}", line),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// The file isn't valid TOML, or an entry is missing fields.
+    Syntax(String),
+    /// The value doesn't fit the setting's type.
+    InvalidValue { expected: &'static str, found: String },
+    /// The entry's `typ` isn't one this tool knows.
+    UnknownType(String),
+    /// The setting matches several registry values, so writing it would be a guess.
+    Ambiguous { candidates: Vec<String> },
+    /// A regedit `.reg` file is malformed.
+    InvalidRegFile(String),
+    /// A filter expression doesn't parse.
+    InvalidFilter { filter: String, message: String },
+    /// Several settings in a config failed at once, e.g. every ambiguous one.
+    Multiple(Vec<SettingsError>),
+}
+
+/// A setting that couldn't be read, with enough context to find and fix it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsError {
+    pub kind: ErrorKind,
+    pub setting: Option<String>,
+    pub location: Option<Location>,
+}
+
+impl SettingsError {
+    pub fn new(kind: ErrorKind) -> Self {
+        SettingsError {
+            kind,
+            setting: None,
+            location: None,
+        }
+    }
+
+    pub fn invalid_value(expected: &'static str, found: impl fmt::Display) -> Self {
+        SettingsError::new(ErrorKind::InvalidValue {
+            expected,
+            found: found.to_string(),
+        })
+    }
+
+    /// Names the setting the error is about, unless a more specific one is already known.
+    pub fn for_setting(mut self, setting: &str) -> Self {
+        self.setting.get_or_insert_with(|| setting.to_string());
+        self
+    }
+
+    pub fn at(mut self, location: Option<Location>) -> Self {
+        if location.is_some() {
+            self.location = location;
+        }
+        self
+    }
+
+    /// Records which file the error came from, keeping any line already known.
+    pub fn in_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.location.get_or_insert_with(Location::default).file = Some(file.into());
+        self
+    }
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+
+        let setting = match &self.setting {
+            Some(setting) => format!("`{}`", setting),
+            None => "The value".to_string(),
+        };
+
+        match &self.kind {
+            ErrorKind::Syntax(message) | ErrorKind::InvalidRegFile(message) if self.setting.is_some() => {
+                write!(f, "{}: {}", setting, message)
+            }
+            ErrorKind::Syntax(message) | ErrorKind::InvalidRegFile(message) => write!(f, "{}", message),
+            ErrorKind::InvalidValue { expected, found } => {
+                write!(f, "{} should be {}, found {}", setting, expected, found)
+            }
+            ErrorKind::UnknownType(typ) => write!(
+                f,
+                "{} has unknown type `{}`, expected one of int, float, bool, axis, color, key, str or raw",
+                setting, typ
+            ),
+            ErrorKind::Ambiguous { candidates } => write!(
+                f,
+                "{} matches several registry values: {}",
+                setting,
+                candidates
+                    .iter()
+                    .map(|c| format!("`{}`", c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ErrorKind::InvalidFilter { filter, message } => write!(f, "Invalid filter `{}`: {}", filter, message),
+            ErrorKind::Multiple(errors) => {
+                write!(f, "{} settings can't be imported:", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
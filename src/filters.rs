@@ -1,5 +1,9 @@
 use lazy_static::lazy_static;
+use regex::Regex;
 
+use crate::error::{ErrorKind, SettingsError};
+
+/// Decides whether a setting is included, by its name and type (e.g. `key`).
 pub trait Filter {
     fn filter(&self, input: &str, typ: &str) -> bool;
 }
@@ -37,13 +41,71 @@ impl Filter for Audio {
 }
 
 #[derive(Clone)]
-pub struct Manual(String);
+pub struct Manual(pub String);
 impl Filter for Manual {
     fn filter(&self, input: &str, _: &str) -> bool {
         input.contains(&self.0)
     }
 }
 
+/// Matches names against a regular expression, anywhere in the name unless it is anchored.
+#[derive(Clone)]
+pub struct RegexFilter(pub Regex);
+impl Filter for RegexFilter {
+    fn filter(&self, input: &str, _: &str) -> bool {
+        self.0.is_match(input)
+    }
+}
+
+/// Matches whole names against a pattern where `*` is any run of characters and `?` is any one character.
+#[derive(Clone)]
+pub struct Glob(pub String);
+impl Filter for Glob {
+    fn filter(&self, input: &str, _: &str) -> bool {
+        let pattern: Vec<char> = self.0.chars().collect();
+        let input: Vec<char> = input.chars().collect();
+        let (mut p, mut i) = (0, 0);
+        // Where the last `*` was, and how much of the input it has taken so far, to backtrack to.
+        let mut star: Option<(usize, usize)> = None;
+
+        while i < input.len() {
+            match pattern.get(p) {
+                Some('*') => {
+                    star = Some((p, i));
+                    p += 1;
+                }
+                Some(&c) if c == '?' || c == input[i] => {
+                    p += 1;
+                    i += 1;
+                }
+                _ => match star {
+                    Some((star_p, star_i)) => {
+                        p = star_p + 1;
+                        i = star_i + 1;
+                        star = Some((star_p, star_i + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        pattern[p..].iter().all(|&c| c == '*')
+    }
+}
+
+/// Matches settings of one type, e.g. `float`, written `type:float`.
+#[derive(Clone)]
+pub struct Type(pub String);
+impl Filter for Type {
+    fn filter(&self, _: &str, typ: &str) -> bool {
+        typ == self.0
+    }
+}
+
+const TYPES: [&str; 8] = ["int", "float", "bool", "axis", "color", "key", "str", "raw"];
+
+/// Any of the filters, so a mix of them can be kept in one list, or combined into a tree with `And`, `Or` and
+/// `Not`.
 #[derive(Clone)]
 pub enum FilterVariant {
     Hitmarkers(Hitmarkers),
@@ -51,6 +113,15 @@ pub enum FilterVariant {
     Sensitivity(Sensitivity),
     Audio(Audio),
     Manual(Manual),
+    Regex(RegexFilter),
+    Glob(Glob),
+    Type(Type),
+    /// Matches what every one of the filters matches.
+    And(Vec<FilterVariant>),
+    /// Matches what any of the filters matches.
+    Or(Vec<FilterVariant>),
+    /// Matches whatever the filter doesn't, written `not name` or `!name`.
+    Not(Box<FilterVariant>),
 }
 
 impl Filter for FilterVariant {
@@ -61,10 +132,28 @@ impl Filter for FilterVariant {
             FilterVariant::Sensitivity(f) => f.filter(input, typ),
             FilterVariant::Audio(f) => f.filter(input, typ),
             FilterVariant::Manual(f) => f.filter(input, typ),
+            FilterVariant::Regex(f) => f.filter(input, typ),
+            FilterVariant::Glob(f) => f.filter(input, typ),
+            FilterVariant::Type(f) => f.filter(input, typ),
+            FilterVariant::And(filters) => filters.iter().all(|f| f.filter(input, typ)),
+            FilterVariant::Or(filters) => filters.iter().any(|f| f.filter(input, typ)),
+            FilterVariant::Not(f) => !f.filter(input, typ),
         }
     }
 }
 
+/// A list of filters includes a setting if any of the others match it and none of the `Not`s exclude it. A list
+/// of only `Not`s includes everything they don't exclude.
+impl Filter for [FilterVariant] {
+    fn filter(&self, input: &str, typ: &str) -> bool {
+        let (excludes, includes): (Vec<&FilterVariant>, Vec<&FilterVariant>) =
+            self.iter().partition(|f| matches!(f, FilterVariant::Not(_)));
+
+        (includes.is_empty() || includes.iter().any(|f| f.filter(input, typ)))
+            && excludes.iter().all(|f| f.filter(input, typ))
+    }
+}
+
 lazy_static! {
     pub static ref COMMON_FILTERS: Vec<FilterVariant> = vec![
         FilterVariant::Hitmarkers(Hitmarkers),
@@ -73,24 +162,359 @@ lazy_static! {
     ];
 }
 
-pub fn parse_filters(filters: Vec<String>) -> Vec<FilterVariant> {
+/// Turns filters, as given on the command line, into filters. Each one is a comma-separated list of expressions,
+/// see [`parse_expression`], and the commas work like giving each expression on its own.
+pub fn parse_filters(filters: Vec<String>) -> Result<Vec<FilterVariant>, SettingsError> {
     let mut result = Vec::new();
+    for f in &filters {
+        result.extend(parse(f, Parser::list)?);
+    }
 
-    for f in filters {
-        let filter = match f.as_str() {
-            "hitmarkers" => FilterVariant::Hitmarkers(*Box::new(Hitmarkers)),
-            "keybindings" => FilterVariant::Keybindings(*Box::new(Keybindings)),
-            "sensitivity" => FilterVariant::Sensitivity(*Box::new(Sensitivity)),
-            "audio" => FilterVariant::Audio(*Box::new(Audio)),
-            "common" => {
-                result.append(COMMON_FILTERS.to_vec().as_mut());
-                continue;
+    Ok(result)
+}
+
+/// Parses a single filter, such as `re:^Hit.*Color` or `glob:*Volume*`. Unknown names match settings containing
+/// them.
+fn parse_term(term: &str) -> Result<FilterVariant, String> {
+    let filter = match term {
+        "hitmarkers" => FilterVariant::Hitmarkers(Hitmarkers),
+        "keybindings" => FilterVariant::Keybindings(Keybindings),
+        "sensitivity" => FilterVariant::Sensitivity(Sensitivity),
+        "audio" => FilterVariant::Audio(Audio),
+        "common" => FilterVariant::Or(COMMON_FILTERS.to_vec()),
+        _ => {
+            if let Some(pattern) = term.strip_prefix("re:") {
+                let regex = Regex::new(pattern).map_err(|e| format!("Invalid regex `{}`: {}", pattern, e))?;
+                FilterVariant::Regex(RegexFilter(regex))
+            } else if let Some(pattern) = term.strip_prefix("glob:") {
+                FilterVariant::Glob(Glob(pattern.to_string()))
+            } else if let Some(typ) = term.strip_prefix("type:") {
+                if !TYPES.contains(&typ) {
+                    return Err(format!("Unknown type `{}`, expected one of {}", typ, TYPES.join(", ")));
+                }
+                FilterVariant::Type(Type(typ.to_string()))
+            } else {
+                FilterVariant::Manual(Manual(term.to_string()))
+            }
+        }
+    };
+
+    Ok(filter)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Comma,
+    Term(&'a str),
+}
+
+impl std::fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "`(`"),
+            Token::Close => write!(f, "`)`"),
+            Token::And => write!(f, "`and`"),
+            Token::Or => write!(f, "`or`"),
+            Token::Not => write!(f, "`not`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Term(term) => write!(f, "`{}`", term),
+        }
+    }
+}
+
+/// How long the term at the start of `input` is. Terms end at whitespace, a comma or a parenthesis, except that a
+/// regex keeps whatever is inside its groups, repetitions and character classes, along with anything escaped.
+fn term_len(input: &str) -> usize {
+    let regex = input.starts_with("re:");
+    let mut depth = 0;
+    let mut class = false;
+    let mut escaped = false;
+
+    for (i, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if regex => escaped = true,
+            ']' if class => class = false,
+            _ if class => {}
+            '[' if regex => class = true,
+            '(' | '{' if regex => depth += 1,
+            ')' | '}' if regex && depth > 0 => depth -= 1,
+            _ if depth > 0 => {}
+            '(' | ')' | ',' => return i,
+            c if c.is_whitespace() => return i,
+            _ => {}
+        }
+    }
+
+    input.len()
+}
+
+fn tokenize(expression: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = match c {
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '!' => (Token::Not, 1),
+            ',' => (Token::Comma, 1),
+            _ => {
+                let len = term_len(rest);
+                let word = &rest[..len];
+                let token = match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Term(word),
+                };
+                (token, len)
             }
-            _ => FilterVariant::Manual(*Box::new(Manual(f.to_string()))),
         };
 
-        result.push(filter);
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+
+    tokens
+}
+
+/// Recursive descent over the tokens, where `not` binds tightest and `or` loosest.
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let found = self.tokens.get(self.position) == Some(&token);
+        if found {
+            self.position += 1;
+        }
+        found
     }
 
-    result
+    fn list(&mut self) -> Result<Vec<FilterVariant>, String> {
+        let mut filters = vec![self.or()?];
+        while self.eat(Token::Comma) {
+            filters.push(self.or()?);
+        }
+
+        Ok(filters)
+    }
+
+    fn or(&mut self) -> Result<FilterVariant, String> {
+        let mut filters = vec![self.and()?];
+        while self.eat(Token::Or) {
+            filters.push(self.and()?);
+        }
+
+        Ok(if filters.len() == 1 { filters.remove(0) } else { FilterVariant::Or(filters) })
+    }
+
+    fn and(&mut self) -> Result<FilterVariant, String> {
+        let mut filters = vec![self.not()?];
+        while self.eat(Token::And) {
+            filters.push(self.not()?);
+        }
+
+        Ok(if filters.len() == 1 { filters.remove(0) } else { FilterVariant::And(filters) })
+    }
+
+    fn not(&mut self) -> Result<FilterVariant, String> {
+        if self.eat(Token::Not) {
+            return Ok(FilterVariant::Not(Box::new(self.not()?)));
+        }
+
+        match self.next() {
+            Some(Token::Term(term)) => parse_term(term),
+            Some(Token::Open) => {
+                let filter = self.or()?;
+                if !self.eat(Token::Close) {
+                    return Err("Missing `)`".to_string());
+                }
+                Ok(filter)
+            }
+            Some(token) => Err(format!("Expected a filter, found {}", token)),
+            None => Err("Expected a filter, found the end".to_string()),
+        }
+    }
+}
+
+/// Parses all of `expression` with `rule`, e.g. [`Parser::or`].
+fn parse<'a, T>(expression: &'a str, rule: fn(&mut Parser<'a>) -> Result<T, String>) -> Result<T, SettingsError> {
+    let invalid = |message: String| {
+        SettingsError::new(ErrorKind::InvalidFilter {
+            filter: expression.to_string(),
+            message,
+        })
+    };
+
+    let mut parser = Parser {
+        tokens: tokenize(expression),
+        position: 0,
+    };
+    let parsed = rule(&mut parser).map_err(invalid)?;
+
+    match parser.next() {
+        None => Ok(parsed),
+        Some(Token::Close) => Err(invalid("Unmatched `)`".to_string())),
+        Some(token) => Err(invalid(format!("Expected `and` or `or` before {}", token))),
+    }
+}
+
+/// Parses a filter expression, e.g. `keybindings and not axis` or `(audio or hitmarkers) and type:float`.
+///
+/// Filters can be combined with `and`, `or` and `not` (or `!`), and grouped with parentheses. `not` binds tightest
+/// and `or` loosest.
+pub fn parse_expression(expression: &str) -> Result<FilterVariant, SettingsError> {
+    parse(expression, Parser::or)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str, input: &str) -> bool {
+        Glob(pattern.to_string()).filter(input, "")
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        assert!(glob("*", "MasterVolume"));
+        assert!(glob("*", ""));
+        assert!(glob("*Volume", "MasterVolume"));
+        assert!(!glob("Volume", "MasterVolume"));
+        assert!(glob("Seat?_key", "Seat2_key"));
+        assert!(!glob("Seat?_key", "Seat_key"));
+        assert!(!glob("Seat?_key", "Seat12_key"));
+    }
+
+    #[test]
+    fn globs_backtrack_over_stars() {
+        assert!(glob("a*b*c", "abc"));
+        assert!(glob("a*b*c", "aXbYbZc"));
+        assert!(glob("a*b*c", "abbcbc"));
+        assert!(!glob("a*b*c", "abcb"));
+        assert!(!glob("a*b*c", "acb"));
+        assert!(glob("**a**", "bab"));
+    }
+
+    #[test]
+    fn empty_globs_only_match_empty_names() {
+        assert!(glob("", ""));
+        assert!(!glob("", "MasterVolume"));
+    }
+
+    #[test]
+    fn exclusions_alone_keep_everything_else() {
+        let filters = parse_filters(vec!["!audio".to_string(), "!glob:*_key".to_string()]).unwrap();
+
+        assert!(filters.filter("MouseSensitivity", "float"));
+        assert!(!filters.filter("MasterVolume", "float"));
+        assert!(!filters.filter("Jump_key", "key"));
+        assert!(parse_filters(Vec::new()).unwrap().filter("Jump_key", "key"));
+    }
+
+    #[test]
+    fn exclusions_narrow_the_other_filters() {
+        let filters = parse_filters(vec!["re:Volume$".to_string(), "!Master".to_string()]).unwrap();
+
+        assert!(filters.filter("MusicVolume", "float"));
+        assert!(!filters.filter("MasterVolume", "float"));
+        assert!(!filters.filter("MouseSensitivity", "float"));
+        assert!(parse_filters(vec!["re:(".to_string()]).is_err());
+    }
+
+    fn matches(expression: &str, name: &str, typ: &str) -> bool {
+        parse_expression(expression).unwrap().filter(name, typ)
+    }
+
+    fn error(expression: &str) -> String {
+        match parse_expression(expression).err().expect("The filter should be invalid").kind {
+            ErrorKind::InvalidFilter { filter, message } => {
+                assert_eq!(filter, expression);
+                message
+            }
+            kind => panic!("Expected an invalid filter, found {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn not_binds_tightest_and_or_loosest() {
+        assert!(matches("Jump or Crouch and type:float", "Jump_key", "key"));
+        assert!(!matches("Jump or Crouch and type:float", "Crouch_key", "key"));
+        assert!(matches("not audio and type:float", "MouseSensitivity", "float"));
+        assert!(!matches("not audio and type:float", "MasterVolume", "float"));
+        assert!(matches("not Master or Music", "MusicVolume", "float"));
+        assert!(matches("Jump AND NOT type:axis", "Jump_key", "key"));
+    }
+
+    #[test]
+    fn bang_is_not() {
+        for (name, typ) in [("Jump_key", "key"), ("MasterVolume", "float")] {
+            assert_eq!(matches("!audio", name, typ), matches("not audio", name, typ));
+            assert_eq!(matches("!!audio", name, typ), matches("audio", name, typ));
+            assert_eq!(matches("keybindings and !(audio)", name, typ), matches("keybindings and not audio", name, typ));
+        }
+    }
+
+    #[test]
+    fn parentheses_nest() {
+        let expression = "((audio or hitmarkers) and not (Master or (type:color)))";
+
+        assert!(matches(expression, "MusicVolume", "float"));
+        assert!(!matches(expression, "MasterVolume", "float"));
+        assert!(!matches(expression, "HitMarkerColor", "color"));
+        assert!(!matches(expression, "Jump_key", "key"));
+    }
+
+    #[test]
+    fn regexes_keep_their_parentheses() {
+        assert!(matches("re:^(Jump|Crouch)_key$", "Crouch_key", "key"));
+        assert!(matches("(re:^Hit(Marker)?Color and type:color)", "HitColor", "color"));
+        assert!(matches("(re:\\)$ or audio)", "Odd)", "str"));
+        assert!(!matches("(re:\\)$ or audio)", "Odd", "str"));
+        assert!(matches("re:^[(]x", "(x", "str"));
+        assert!(matches("re:^Seat( [0-9])", "Seat 2", "str"));
+    }
+
+    #[test]
+    fn commas_separate_filters() {
+        let filters = parse_filters(vec!["audio and not Music, !Master".to_string()]).unwrap();
+        assert_eq!(filters.len(), 2);
+        assert!(!filters.filter("MasterVolume", "float"));
+        assert!(filters.filter("VoiceVolume", "float"));
+        assert!(!filters.filter("MusicVolume", "float"));
+
+        let filters = parse_filters(vec!["re:^(Jump|Crouch){1,2}_key,re:[,]".to_string()]).unwrap();
+        assert_eq!(filters.len(), 2);
+        assert!(filters.filter("Jump_key", "key"));
+        assert!(filters.filter("a,b", "str"));
+        assert!(!filters.filter("Reload_key", "key"));
+    }
+
+    #[test]
+    fn reports_where_expressions_go_wrong() {
+        assert_eq!(error("audio)"), "Unmatched `)`");
+        assert_eq!(error("(audio or (Jump)"), "Missing `)`");
+        assert_eq!(error("audio and"), "Expected a filter, found the end");
+        assert_eq!(error(""), "Expected a filter, found the end");
+        assert_eq!(error("audio or )"), "Expected a filter, found `)`");
+        assert_eq!(error("glob:* Volume"), "Expected `and` or `or` before `Volume`");
+        assert_eq!(error("audio, Jump"), "Expected `and` or `or` before `,`");
+        assert!(error("type:number").starts_with("Unknown type `number`"));
+        assert!(error("re:(").starts_with("Invalid regex `(`"));
+    }
 }
@@ -0,0 +1,425 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    battlebit::{RegType, RegValue, BBR_REG_SUBKEY},
+    storage::Storage,
+};
+
+// Offsets in a hive are relative to the first hbin, which follows the 4KiB base block.
+const HBIN_START: usize = 0x1000;
+
+const KEY_COMP_NAME: u16 = 0x0020;
+const VALUE_COMP_NAME: u16 = 0x0001;
+
+// Values bigger than this are split across the segments of a `db` record.
+const BIG_DATA_SEGMENT: usize = 16344;
+const DATA_INLINE: u32 = 0x8000_0000;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn u16_at(data: &[u8], at: usize) -> io::Result<u16> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid("Unexpected end of hive cell"))
+}
+
+fn u32_at(data: &[u8], at: usize) -> io::Result<u32> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("Unexpected end of hive cell"))
+}
+
+fn decode_name(bytes: &[u8], compressed: bool) -> String {
+    if compressed {
+        bytes.iter().map(|&b| b as char).collect()
+    } else {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    }
+}
+
+struct Regf<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Regf<'a> {
+    fn new(data: &'a [u8]) -> io::Result<Self> {
+        if data.get(..4) != Some(b"regf") {
+            return Err(invalid("Not a registry hive, missing the `regf` signature"));
+        }
+        if data.get(HBIN_START..HBIN_START + 4) != Some(b"hbin") {
+            return Err(invalid("Registry hive has no `hbin` after the base block"));
+        }
+
+        Ok(Regf { data })
+    }
+
+    fn root(&self) -> io::Result<u32> {
+        u32_at(self.data, 0x24)
+    }
+
+    /// The contents of the cell at `offset`, without its size header.
+    fn cell(&self, offset: u32) -> io::Result<&'a [u8]> {
+        let start = HBIN_START + offset as usize;
+        let size = u32_at(self.data, start)? as i32;
+        // Allocated cells have a negative size.
+        let size = size.unsigned_abs() as usize;
+
+        if size < 4 {
+            return Err(invalid(format!("Invalid hive cell at {:#x}", offset)));
+        }
+
+        self.data
+            .get(start + 4..start + size)
+            .ok_or_else(|| invalid(format!("Hive cell at {:#x} runs past the end of the file", offset)))
+    }
+
+    fn key(&self, offset: u32) -> io::Result<&'a [u8]> {
+        let nk = self.cell(offset)?;
+        if nk.get(..2) != Some(b"nk") {
+            return Err(invalid(format!("Expected a key record at {:#x}", offset)));
+        }
+        Ok(nk)
+    }
+
+    fn key_name(&self, nk: &[u8]) -> io::Result<String> {
+        let flags = u16_at(nk, 0x02)?;
+        let len = u16_at(nk, 0x48)? as usize;
+        let name = nk
+            .get(0x4C..0x4C + len)
+            .ok_or_else(|| invalid("Key name runs past the end of its cell"))?;
+        Ok(decode_name(name, flags & KEY_COMP_NAME != 0))
+    }
+
+    fn subkeys(&self, nk: &[u8]) -> io::Result<Vec<u32>> {
+        if u32_at(nk, 0x14)? == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut keys = Vec::new();
+        self.collect_subkeys(u32_at(nk, 0x1C)?, &mut keys)?;
+        Ok(keys)
+    }
+
+    fn collect_subkeys(&self, list: u32, keys: &mut Vec<u32>) -> io::Result<()> {
+        let cell = self.cell(list)?;
+        let count = u16_at(cell, 0x02)? as usize;
+
+        match cell.get(..2) {
+            Some(b"lf") | Some(b"lh") => {
+                for i in 0..count {
+                    keys.push(u32_at(cell, 4 + i * 8)?);
+                }
+            }
+            Some(b"li") => {
+                for i in 0..count {
+                    keys.push(u32_at(cell, 4 + i * 4)?);
+                }
+            }
+            Some(b"ri") => {
+                for i in 0..count {
+                    self.collect_subkeys(u32_at(cell, 4 + i * 4)?, keys)?;
+                }
+            }
+            _ => return Err(invalid(format!("Unknown subkey list at {:#x}", list))),
+        }
+
+        Ok(())
+    }
+
+    fn find_subkey(&self, nk: &[u8], name: &str) -> io::Result<Option<&'a [u8]>> {
+        for offset in self.subkeys(nk)? {
+            let subkey = self.key(offset)?;
+            if self.key_name(subkey)?.eq_ignore_ascii_case(name) {
+                return Ok(Some(subkey));
+            }
+        }
+        Ok(None)
+    }
+
+    fn values(&self, nk: &[u8]) -> io::Result<Vec<(String, RegValue)>> {
+        let count = u32_at(nk, 0x24)? as usize;
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let list = self.cell(u32_at(nk, 0x28)?)?;
+        let mut values = Vec::with_capacity(count);
+
+        for i in 0..count {
+            let offset = u32_at(list, i * 4)?;
+            let vk = self.cell(offset)?;
+            if vk.get(..2) != Some(b"vk") {
+                return Err(invalid(format!("Expected a value record at {:#x}", offset)));
+            }
+
+            let name_len = u16_at(vk, 0x02)? as usize;
+            let flags = u16_at(vk, 0x10)?;
+            let name = vk
+                .get(0x14..0x14 + name_len)
+                .ok_or_else(|| invalid("Value name runs past the end of its cell"))?;
+            let name = decode_name(name, flags & VALUE_COMP_NAME != 0);
+
+            let vtype = u32_at(vk, 0x0C)?;
+            let vtype = match RegType::from_u32(vtype) {
+                Some(t) => t,
+                None => {
+                    tracing::warn!("Skipping hive value `{}` with unknown type {}", name, vtype);
+                    continue;
+                }
+            };
+
+            values.push((
+                name,
+                RegValue {
+                    bytes: self.value_data(vk)?,
+                    vtype,
+                },
+            ));
+        }
+
+        Ok(values)
+    }
+
+    fn value_data(&self, vk: &[u8]) -> io::Result<Vec<u8>> {
+        let size = u32_at(vk, 0x04)?;
+        let offset = u32_at(vk, 0x08)?;
+
+        if size & DATA_INLINE != 0 {
+            let size = (size & !DATA_INLINE) as usize;
+            return Ok(offset.to_le_bytes()[..size.min(4)].to_vec());
+        }
+
+        let size = size as usize;
+        let cell = self.cell(offset)?;
+
+        if size > BIG_DATA_SEGMENT && cell.get(..2) == Some(b"db") {
+            let segments = u16_at(cell, 0x02)? as usize;
+            let list = self.cell(u32_at(cell, 0x04)?)?;
+
+            let mut data = Vec::with_capacity(size);
+            for i in 0..segments {
+                let segment = self.cell(u32_at(list, i * 4)?)?;
+                data.extend_from_slice(&segment[..segment.len().min(BIG_DATA_SEGMENT)]);
+            }
+            data.truncate(size);
+            return Ok(data);
+        }
+
+        cell.get(..size)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| invalid("Value data runs past the end of its cell"))
+    }
+}
+
+/// The Battlebit key inside an offline `NTUSER.DAT` hive. Hives are only ever read, never written.
+pub struct Hive {
+    path: PathBuf,
+    values: Vec<(String, RegValue)>,
+}
+
+impl Hive {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        let regf = Regf::new(&data)?;
+
+        let mut key = regf.key(regf.root()?)?;
+        for part in BBR_REG_SUBKEY.split('\\') {
+            key = regf.find_subkey(key, part)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Hive does not contain `{}`", BBR_REG_SUBKEY),
+                )
+            })?;
+        }
+
+        Ok(Hive {
+            path: std::fs::canonicalize(path)?,
+            values: regf.values(key)?,
+        })
+    }
+}
+
+impl Storage for Hive {
+    fn values(&self) -> io::Result<Vec<(String, RegValue)>> {
+        Ok(self.values.clone())
+    }
+
+    fn set_value(&mut self, _: &str, _: &RegValue) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Registry hives are read-only",
+        ))
+    }
+
+    fn delete_value(&mut self, _: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Registry hives are read-only",
+        ))
+    }
+
+    fn source(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the cells of a hive bin, each at an offset relative to the start of the bin.
+    struct Builder {
+        bin: Vec<u8>,
+    }
+
+    impl Builder {
+        fn new() -> Self {
+            let mut bin = b"hbin".to_vec();
+            bin.resize(0x20, 0);
+            Builder { bin }
+        }
+
+        fn cell(&mut self, data: &[u8]) -> u32 {
+            let offset = self.bin.len() as u32;
+            let size = (data.len() + 4).next_multiple_of(8);
+            self.bin.extend((-(size as i32)).to_le_bytes());
+            self.bin.extend(data);
+            self.bin.resize(offset as usize + size, 0);
+            offset
+        }
+
+        fn key(&mut self, name: &str, subkeys: &[u32], values: &[u32]) -> u32 {
+            let mut nk = vec![0; 0x4C];
+            nk[..2].copy_from_slice(b"nk");
+            nk[0x02..0x04].copy_from_slice(&KEY_COMP_NAME.to_le_bytes());
+
+            if !subkeys.is_empty() {
+                let mut lf = b"lf".to_vec();
+                lf.extend((subkeys.len() as u16).to_le_bytes());
+                for subkey in subkeys {
+                    lf.extend(subkey.to_le_bytes());
+                    lf.extend([0; 4]);
+                }
+                nk[0x14..0x18].copy_from_slice(&(subkeys.len() as u32).to_le_bytes());
+                nk[0x1C..0x20].copy_from_slice(&self.cell(&lf).to_le_bytes());
+            }
+
+            if !values.is_empty() {
+                let list: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                nk[0x24..0x28].copy_from_slice(&(values.len() as u32).to_le_bytes());
+                nk[0x28..0x2C].copy_from_slice(&self.cell(&list).to_le_bytes());
+            }
+
+            nk[0x48..0x4A].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            nk.extend(name.as_bytes());
+            self.cell(&nk)
+        }
+
+        fn value(&mut self, name: &str, value: &RegValue) -> u32 {
+            let (size, offset) = if value.bytes.len() <= 4 {
+                let mut inline = [0; 4];
+                inline[..value.bytes.len()].copy_from_slice(&value.bytes);
+                (value.bytes.len() as u32 | DATA_INLINE, u32::from_le_bytes(inline))
+            } else {
+                (value.bytes.len() as u32, self.cell(&value.bytes))
+            };
+
+            let mut vk = vec![0; 0x14];
+            vk[..2].copy_from_slice(b"vk");
+            vk[0x02..0x04].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            vk[0x04..0x08].copy_from_slice(&size.to_le_bytes());
+            vk[0x08..0x0C].copy_from_slice(&offset.to_le_bytes());
+            vk[0x0C..0x10].copy_from_slice(&(value.vtype as u32).to_le_bytes());
+            vk[0x10..0x12].copy_from_slice(&VALUE_COMP_NAME.to_le_bytes());
+            vk.extend(name.as_bytes());
+            self.cell(&vk)
+        }
+    }
+
+    fn values() -> Vec<(String, RegValue)> {
+        vec![
+            (
+                "MasterVolume_float_h717095934".to_string(),
+                RegValue {
+                    bytes: 0.3f64.to_le_bytes().to_vec(),
+                    vtype: RegType::REG_DWORD,
+                },
+            ),
+            (
+                "Jump_key_h123".to_string(),
+                RegValue {
+                    bytes: 32i32.to_le_bytes().to_vec(),
+                    vtype: RegType::REG_DWORD,
+                },
+            ),
+            (
+                "PlayerName_h99".to_string(),
+                RegValue {
+                    bytes: b"abc\0".to_vec(),
+                    vtype: RegType::REG_BINARY,
+                },
+            ),
+            (
+                "Big_h1".to_string(),
+                RegValue {
+                    bytes: (0..200).collect(),
+                    vtype: RegType::REG_BINARY,
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn reads_the_battlebit_key() {
+        let mut builder = Builder::new();
+        let cells: Vec<u32> = values().iter().map(|(name, value)| builder.value(name, value)).collect();
+        let battlebit = builder.key("BattleBit", &[], &cells);
+        let dev_team = builder.key("BattleBitDevTeam", &[battlebit], &[]);
+        let microsoft = builder.key("Microsoft", &[], &[]);
+        // Saved with a different case than `BBR_REG_SUBKEY`, which lookups have to ignore.
+        let software = builder.key("Software", &[microsoft, dev_team], &[]);
+        let root = builder.key("ROOT", &[software], &[]);
+
+        let mut hive = vec![0; HBIN_START];
+        hive[..4].copy_from_slice(b"regf");
+        hive[0x24..0x28].copy_from_slice(&root.to_le_bytes());
+        hive.extend(builder.bin);
+
+        let dir = std::env::temp_dir().join(format!("bbr-settings-editor-{}-hive", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("NTUSER.DAT");
+        std::fs::write(&path, hive).unwrap();
+
+        let read = Hive::open(&path).unwrap().values().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(read, values());
+    }
+
+    #[test]
+    fn refuses_writes() {
+        let mut hive = Hive {
+            path: PathBuf::from("NTUSER.DAT"),
+            values: values(),
+        };
+
+        assert!(hive.set_value("Jump_key_h123", &values()[1].1).is_err());
+        assert!(hive.delete_value("Jump_key_h123").is_err());
+        assert_eq!(hive.values().unwrap(), values());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let error = Regf::new(b"WINE REGISTRY Version 2").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
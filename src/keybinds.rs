@@ -0,0 +1,390 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use crate::{
+    battlebit::{decode_regedit_name, BattlebitType, Key, State},
+    error::SettingsError,
+    toml,
+};
+
+/// When a binding is active, guessed from its setting name. Bindings only clash with others that can be
+/// active at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Context {
+    /// Menus, chat, voice and the like, which work everywhere.
+    Global,
+    Infantry,
+    Vehicle,
+    Spectator,
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Context::Global => write!(f, "global"),
+            Context::Infantry => write!(f, "infantry"),
+            Context::Vehicle => write!(f, "vehicle"),
+            Context::Spectator => write!(f, "spectator"),
+        }
+    }
+}
+
+/// Words that hint at a context. They are matched against the start of each word of a setting name, so `heli`
+/// matches `HelicopterUp_key` but `car` doesn't match `Discard_key`.
+const GLOBAL_HINTS: &[&str] = &["menu", "chat", "voice", "scoreboard", "map", "console", "screenshot"];
+const VEHICLE_HINTS: &[&str] = &[
+    "vehicle", "heli", "plane", "jet", "boat", "tank", "car", "drive", "pilot", "gunner", "seat", "throttle",
+];
+const SPECTATOR_HINTS: &[&str] = &["spectat", "freecam"];
+
+/// The words of a setting name, lowercased, split at underscores and where camel case starts a new word.
+fn words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            words.push(std::mem::take(&mut word));
+            continue;
+        }
+
+        // `HUDMenu` is `hud` and `menu`, `MapZoom` is `map` and `zoom`.
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let starts_word = c.is_uppercase()
+            && previous.is_some_and(|p| {
+                p.is_lowercase() || p.is_numeric() || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if starts_word {
+            words.push(std::mem::take(&mut word));
+        }
+
+        word.extend(c.to_lowercase());
+    }
+    words.push(word);
+
+    words.retain(|w| !w.is_empty());
+    words
+}
+
+/// Guesses when the binding called `name` is active.
+pub fn context(name: &str) -> Context {
+    let words = words(name);
+    let matches = |hints: &[&str]| hints.iter().any(|hint| words.iter().any(|word| word.starts_with(hint)));
+
+    if matches(SPECTATOR_HINTS) {
+        Context::Spectator
+    } else if matches(VEHICLE_HINTS) {
+        Context::Vehicle
+    } else if matches(GLOBAL_HINTS) {
+        Context::Global
+    } else {
+        Context::Infantry
+    }
+}
+
+/// Several bindings on the same key that can be active at the same time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: i32,
+    pub context: Context,
+    /// The clashing settings, sorted by name.
+    pub settings: Vec<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}",
+            Key(self.key).name(),
+            self.context,
+            self.settings
+                .iter()
+                .map(|s| format!("`{}`", s))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+/// Finds every key bound more than once within a context, counting global bindings in every context.
+pub fn conflicts<'a>(settings: impl IntoIterator<Item = (&'a str, &'a BattlebitType)>) -> Vec<Conflict> {
+    let mut by_key: BTreeMap<i32, BTreeMap<Context, Vec<String>>> = BTreeMap::new();
+
+    for (name, value) in settings {
+        // Unbound keys are `None`, which everything shares.
+        if let BattlebitType::Key(Key(key)) = value {
+            if *key != 0 {
+                by_key
+                    .entry(*key)
+                    .or_default()
+                    .entry(context(name))
+                    .or_default()
+                    .push(name.to_string());
+            }
+        }
+    }
+
+    let mut conflicts = Vec::new();
+    for (key, mut contexts) in by_key {
+        let global = contexts.remove(&Context::Global).unwrap_or_default();
+
+        if contexts.is_empty() && global.len() > 1 {
+            conflicts.push((key, Context::Global, global.clone()));
+        }
+        for (context, names) in contexts {
+            let clashing: Vec<String> = names.into_iter().chain(global.iter().cloned()).collect();
+            if clashing.len() > 1 {
+                conflicts.push((key, context, clashing));
+            }
+        }
+    }
+
+    conflicts
+        .into_iter()
+        .map(|(key, context, mut settings)| {
+            settings.sort();
+            Conflict {
+                key,
+                context,
+                settings,
+            }
+        })
+        .collect()
+}
+
+/// The settings as they will be once the pending changes are saved, by setting name.
+fn pending_settings(state: &State) -> HashMap<String, BattlebitType> {
+    let mut settings: HashMap<String, BattlebitType> = state.registry.clone();
+
+    for regedit_name in &state.deleted_registry {
+        settings.remove(&decode_regedit_name(regedit_name).0);
+    }
+    for (regedit_name, value) in &state.updated_registry {
+        settings.insert(decode_regedit_name(regedit_name).0, value.decoded(regedit_name));
+    }
+
+    settings
+}
+
+/// The conflicts saving the pending changes would create, ignoring any that are already there.
+pub fn introduced_conflicts(state: &State) -> Vec<Conflict> {
+    let before = conflicts(state.registry.iter().map(|(name, value)| (name.as_str(), value)));
+    let after = pending_settings(state);
+
+    conflicts(after.iter().map(|(name, value)| (name.as_str(), value)))
+        .into_iter()
+        .filter(|conflict| !before.contains(conflict))
+        .collect()
+}
+
+/// Where bindings move to, by `KeyCode`. Keys it doesn't mention stay where they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyMap(HashMap<i32, Option<i32>>);
+
+impl KeyMap {
+    /// Moves `from` to `to`, or marks it as having nowhere to go with `None`.
+    pub fn insert(&mut self, from: i32, to: Option<i32>) {
+        self.0.insert(from, to);
+    }
+
+    /// Where `key` moves to, or `None` if it can't be moved.
+    pub fn get(&self, key: i32) -> Option<i32> {
+        self.0.get(&key).copied().unwrap_or(Some(key))
+    }
+}
+
+/// A binding a [`KeyMap`] had nowhere to move, so it was left as it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unmapped {
+    pub setting: String,
+    pub key: i32,
+}
+
+impl fmt::Display for Unmapped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` ({})", self.setting, Key(self.key).name())
+    }
+}
+
+/// Queues every binding in `state` to move where `map` says, returning the ones it couldn't move.
+pub fn remap(state: &mut State, map: &KeyMap) -> Result<Vec<Unmapped>, SettingsError> {
+    let mut moved = Vec::new();
+    let mut unmapped = Vec::new();
+
+    for (name, value) in &state.registry {
+        if let BattlebitType::Key(Key(key)) = value {
+            match map.get(*key) {
+                Some(new) if new != *key => moved.push((name.clone(), new)),
+                Some(_) => {}
+                None => unmapped.push(Unmapped {
+                    setting: name.clone(),
+                    key: *key,
+                }),
+            }
+        }
+    }
+
+    for (name, key) in moved {
+        state.update_registry(&name, BattlebitType::Key(Key(key)))?;
+    }
+
+    unmapped.sort_by(|a, b| a.setting.cmp(&b.setting));
+    Ok(unmapped)
+}
+
+/// Moves every binding in a TOML config where `map` says, returning the new config and the bindings it
+/// couldn't move.
+pub fn remap_toml(config: &str, map: &KeyMap) -> Result<(String, Vec<Unmapped>), SettingsError> {
+    let mut unmapped = Vec::new();
+
+    let config = toml::map_keys(config, |name, Key(key)| match map.get(key) {
+        Some(new) => Key(new),
+        None => {
+            unmapped.push(Unmapped {
+                setting: name.to_string(),
+                key,
+            });
+            Key(key)
+        }
+    })?;
+
+    Ok((config, unmapped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_names_into_words() {
+        assert_eq!(words("HeliThrottle_Up_key"), ["heli", "throttle", "up", "key"]);
+        assert_eq!(words("HUDMenu_key"), ["hud", "menu", "key"]);
+        assert_eq!(words("Seat2Swap_key"), ["seat2", "swap", "key"]);
+    }
+
+    #[test]
+    fn guesses_contexts_by_word() {
+        assert_eq!(context("Jump_key"), Context::Infantry);
+        assert_eq!(context("Discard_key"), Context::Infantry);
+        assert_eq!(context("Bitmap_key"), Context::Infantry);
+        assert_eq!(context("Map_key"), Context::Global);
+        assert_eq!(context("OpenChat_key"), Context::Global);
+        assert_eq!(context("CarHorn_key"), Context::Vehicle);
+        assert_eq!(context("HelicopterUp_key"), Context::Vehicle);
+        assert_eq!(context("SpectatorNext_key"), Context::Spectator);
+    }
+
+    fn key(code: i32) -> BattlebitType {
+        BattlebitType::Key(Key(code))
+    }
+
+    #[test]
+    fn finds_keys_bound_twice_in_a_context() {
+        let settings = [
+            ("Jump_key", key(32)),
+            ("Crouch_key", key(32)),
+            ("VehicleBoost_key", key(32)),
+            ("Reload_key", key(114)),
+            ("VehicleExit_key", key(114)),
+            ("Map_key", key(109)),
+            ("Melee_key", key(109)),
+            ("Fire_key", key(0)),
+            ("Aim_key", key(0)),
+        ];
+
+        let conflicts = conflicts(settings.iter().map(|(name, value)| (*name, value)));
+
+        assert_eq!(
+            conflicts,
+            [
+                Conflict {
+                    key: 32,
+                    context: Context::Infantry,
+                    settings: vec!["Crouch_key".to_string(), "Jump_key".to_string()],
+                },
+                Conflict {
+                    key: 109,
+                    context: Context::Infantry,
+                    settings: vec!["Map_key".to_string(), "Melee_key".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn global_bindings_clash_with_each_other() {
+        let settings = [("Map_key", key(109)), ("OpenChat_key", key(109))];
+
+        let conflicts = conflicts(settings.iter().map(|(name, value)| (*name, value)));
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].context, Context::Global);
+        assert_eq!(conflicts[0].to_string(), "M (global): `Map_key`, `OpenChat_key`");
+    }
+
+    #[test]
+    fn only_reports_conflicts_an_import_introduces() {
+        use crate::{
+            battlebit::{Raw, RegType, RegValue},
+            storage::MemoryStorage,
+        };
+
+        let dword = |code: i32| RegValue {
+            bytes: code.to_le_bytes().to_vec(),
+            vtype: RegType::REG_DWORD,
+        };
+        let storage = MemoryStorage::new(vec![
+            ("Jump_key_h1".to_string(), dword(32)),
+            ("Reload_key_h2".to_string(), dword(114)),
+            ("Interact_key_h3".to_string(), dword(114)),
+        ]);
+        let mut state = State::new(Box::new(storage)).unwrap();
+
+        state.from_toml_str("[Crouch_key]\ntyp = \"key\"\nvalue = \"Space\"\n").unwrap();
+
+        let conflicts = introduced_conflicts(&state);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].settings, ["Crouch_key", "Jump_key"]);
+
+        // Values imported from a `.reg` file are kept raw, but still hold keys.
+        let melee = BattlebitType::Raw(Raw(dword(32)));
+        state.updated_registry.insert("Melee_key_h4".to_string(), melee);
+        assert_eq!(introduced_conflicts(&state)[0].settings, ["Crouch_key", "Jump_key", "Melee_key"]);
+    }
+
+    #[test]
+    fn remapping_a_config_keeps_everything_but_the_keys() {
+        let config = r#"# Shared by a friend on AZERTY
+[Walk_key]
+typ = "key"
+value = "Z" # forward
+
+[MasterVolume]
+typ = "float"
+value = 0.5
+
+[Lean_key]
+typ = "key"
+value = "Quote"
+"#;
+        let mut map = KeyMap::default();
+        map.insert('z' as i32, Some('w' as i32));
+        map.insert('\'' as i32, None);
+
+        let (remapped, unmapped) = remap_toml(config, &map).unwrap();
+
+        assert_eq!(remapped, config.replace(r#"value = "Z""#, r#"value = "W""#));
+        assert_eq!(
+            unmapped,
+            [Unmapped {
+                setting: "Lean_key".to_string(),
+                key: '\'' as i32,
+            }]
+        );
+    }
+}
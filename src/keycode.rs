@@ -0,0 +1,163 @@
+use lazy_static::lazy_static;
+
+/// Unity `KeyCode`s that aren't part of a numbered range, by their Unity name.
+const NAMED_KEYS: &[(&str, i32)] = &[
+    ("None", 0),
+    ("Backspace", 8),
+    ("Tab", 9),
+    ("Clear", 12),
+    ("Return", 13),
+    ("Pause", 19),
+    ("Escape", 27),
+    ("Space", 32),
+    ("Exclaim", 33),
+    ("DoubleQuote", 34),
+    ("Hash", 35),
+    ("Dollar", 36),
+    ("Percent", 37),
+    ("Ampersand", 38),
+    ("Quote", 39),
+    ("LeftParen", 40),
+    ("RightParen", 41),
+    ("Asterisk", 42),
+    ("Plus", 43),
+    ("Comma", 44),
+    ("Minus", 45),
+    ("Period", 46),
+    ("Slash", 47),
+    ("Colon", 58),
+    ("Semicolon", 59),
+    ("Less", 60),
+    ("Equals", 61),
+    ("Greater", 62),
+    ("Question", 63),
+    ("At", 64),
+    ("LeftBracket", 91),
+    ("Backslash", 92),
+    ("RightBracket", 93),
+    ("Caret", 94),
+    ("Underscore", 95),
+    ("BackQuote", 96),
+    ("LeftCurlyBracket", 123),
+    ("Pipe", 124),
+    ("RightCurlyBracket", 125),
+    ("Tilde", 126),
+    ("Delete", 127),
+    ("KeypadPeriod", 266),
+    ("KeypadDivide", 267),
+    ("KeypadMultiply", 268),
+    ("KeypadMinus", 269),
+    ("KeypadPlus", 270),
+    ("KeypadEnter", 271),
+    ("KeypadEquals", 272),
+    ("UpArrow", 273),
+    ("DownArrow", 274),
+    ("RightArrow", 275),
+    ("LeftArrow", 276),
+    ("Insert", 277),
+    ("Home", 278),
+    ("End", 279),
+    ("PageUp", 280),
+    ("PageDown", 281),
+    ("Numlock", 300),
+    ("CapsLock", 301),
+    ("ScrollLock", 302),
+    ("RightShift", 303),
+    ("LeftShift", 304),
+    ("RightControl", 305),
+    ("LeftControl", 306),
+    ("RightAlt", 307),
+    ("LeftAlt", 308),
+    ("RightCommand", 309),
+    ("LeftCommand", 310),
+    ("LeftWindows", 311),
+    ("RightWindows", 312),
+    ("AltGr", 313),
+    ("Help", 315),
+    ("Print", 316),
+    ("SysReq", 317),
+    ("Break", 318),
+    ("Menu", 319),
+];
+
+/// Other names Unity has for the same codes. They are accepted, but never written.
+const ALIASES: &[(&str, i32)] = &[
+    ("RightApple", 309),
+    ("RightMeta", 309),
+    ("LeftApple", 310),
+    ("LeftMeta", 310),
+];
+
+lazy_static! {
+    /// Every Unity `KeyCode` by name, in code order, without the aliases.
+    pub static ref KEY_CODES: Vec<(String, i32)> = {
+        let mut codes: Vec<(String, i32)> = NAMED_KEYS
+            .iter()
+            .map(|&(name, code)| (name.to_string(), code))
+            .collect();
+
+        codes.extend((0..10).map(|i| (format!("Alpha{}", i), 48 + i)));
+        codes.extend((b'A'..=b'Z').map(|c| ((c as char).to_string(), (c.to_ascii_lowercase()) as i32)));
+        codes.extend((0..10).map(|i| (format!("Keypad{}", i), 256 + i)));
+        codes.extend((1..=15).map(|i| (format!("F{}", i), 281 + i)));
+        codes.extend((0..7).map(|i| (format!("Mouse{}", i), 323 + i)));
+        codes.extend((0..20).map(|i| (format!("JoystickButton{}", i), 330 + i)));
+        for joystick in 1..=8 {
+            codes.extend((0..20).map(|i| (format!("Joystick{}Button{}", joystick, i), 330 + joystick * 20 + i)));
+        }
+
+        codes.sort_by_key(|&(_, code)| code);
+        codes
+    };
+}
+
+/// The Unity name of a `KeyCode`, e.g. `LeftShift` for 304.
+pub fn name(code: i32) -> Option<&'static str> {
+    KEY_CODES
+        .binary_search_by_key(&code, |&(_, c)| c)
+        .ok()
+        .map(|i| KEY_CODES[i].0.as_str())
+}
+
+/// The `KeyCode` called `name`, ignoring case.
+pub fn from_name(name: &str) -> Option<i32> {
+    KEY_CODES
+        .iter()
+        .map(|(n, code)| (n.as_str(), *code))
+        .chain(ALIASES.iter().copied())
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, code)| code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_codes_round_trip() {
+        for (name, code) in KEY_CODES.iter() {
+            assert_eq!(from_name(name), Some(*code), "{}", name);
+            assert_eq!(self::name(*code), Some(name.as_str()), "{}", code);
+        }
+    }
+
+    #[test]
+    fn knows_unity_codes() {
+        assert_eq!(name(304), Some("LeftShift"));
+        assert_eq!(name(97), Some("A"));
+        assert_eq!(name(49), Some("Alpha1"));
+        assert_eq!(name(323), Some("Mouse0"));
+        assert_eq!(name(350), Some("Joystick1Button0"));
+        assert_eq!(name(509), Some("Joystick8Button19"));
+        assert_eq!(name(-1), None);
+        assert_eq!(name(1000), None);
+    }
+
+    #[test]
+    fn accepts_aliases_and_any_case() {
+        assert_eq!(from_name("leftshift"), Some(304));
+        assert_eq!(from_name("LeftMeta"), from_name("LeftCommand"));
+        assert_eq!(name(310), Some("LeftCommand"));
+        assert_eq!(from_name("NotAKey"), None);
+    }
+}
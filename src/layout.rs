@@ -0,0 +1,132 @@
+use std::{fmt, str::FromStr};
+
+use crate::keybinds::KeyMap;
+
+/// A keyboard layout, for moving bindings to the same physical keys on another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Dvorak,
+}
+
+// What each layout types on the same physical keys: the key left of 1, the two right of 0, then the top,
+// home and bottom letter rows and finally the key above Enter. Keys that type something Unity has no `KeyCode`
+// for are written as that character anyway, so they line up, and can't be mapped to.
+const QWERTY: &str = concat!("`-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./", "\\");
+const AZERTY: &str = concat!("²)=", "azertyuiop^$", "qsdfghjklmù", "wxcvbn,;:!", "*");
+const QWERTZ: &str = concat!("^ß´", "qwertzuiopü+", "asdfghjklöä", "yxcvbnm,.-", "#");
+const DVORAK: &str = concat!("`[]", "',.pyfgcrl/=", "aoeuidhtns-", ";qjkxbmwvz", "\\");
+
+impl Layout {
+    pub const ALL: [Layout; 4] = [Layout::Qwerty, Layout::Azerty, Layout::Qwertz, Layout::Dvorak];
+
+    fn keys(&self) -> &'static str {
+        match self {
+            Layout::Qwerty => QWERTY,
+            Layout::Azerty => AZERTY,
+            Layout::Qwertz => QWERTZ,
+            Layout::Dvorak => DVORAK,
+        }
+    }
+
+    /// Moves bindings made on this layout to the keys in the same place on `to`.
+    ///
+    /// Only the letter block moves. The number row, function keys, arrows, the keypad and the mouse are the
+    /// same everywhere and are left alone.
+    pub fn translation(&self, to: Layout) -> KeyMap {
+        let mut map = KeyMap::default();
+
+        for (from, to) in self.keys().chars().zip(to.keys().chars()) {
+            // A key that types something without a `KeyCode` here can't have been bound to begin with.
+            if let Some(from) = key_code(from) {
+                map.insert(from, key_code(to));
+            }
+        }
+
+        map
+    }
+}
+
+/// The `KeyCode` of the key typing `c`, which for printable ASCII is the lowercase character itself.
+fn key_code(c: char) -> Option<i32> {
+    c.is_ascii_graphic().then_some(c.to_ascii_lowercase() as i32)
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layout::Qwerty => write!(f, "qwerty"),
+            Layout::Azerty => write!(f, "azerty"),
+            Layout::Qwertz => write!(f, "qwertz"),
+            Layout::Dvorak => write!(f, "dvorak"),
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Layout::ALL
+            .into_iter()
+            .find(|layout| layout.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown layout `{}`, expected one of qwerty, azerty, qwertz or dvorak", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(c: char) -> i32 {
+        c as i32
+    }
+
+    #[test]
+    fn moves_keys_to_the_same_place() {
+        let map = Layout::Qwerty.translation(Layout::Azerty);
+
+        assert_eq!(map.get(code('q')), Some(code('a')));
+        assert_eq!(map.get(code('w')), Some(code('z')));
+        assert_eq!(map.get(code('a')), Some(code('q')));
+        assert_eq!(map.get(code(';')), Some(code('m')));
+        assert_eq!(map.get(code('m')), Some(code(',')));
+    }
+
+    #[test]
+    fn leaves_keys_outside_the_letter_block_alone() {
+        let map = Layout::Qwerty.translation(Layout::Dvorak);
+
+        for key in [code('1'), 282, 273, 256, 323] {
+            assert_eq!(map.get(key), Some(key));
+        }
+    }
+
+    #[test]
+    fn keys_without_a_key_code_cant_be_moved() {
+        assert_eq!(Layout::Qwerty.translation(Layout::Azerty).get(code('\'')), None);
+        assert_eq!(Layout::Qwerty.translation(Layout::Qwertz).get(code('[')), None);
+    }
+
+    #[test]
+    fn moving_there_and_back_keeps_the_letters() {
+        for from in Layout::ALL {
+            for to in Layout::ALL {
+                let (there, back) = (from.translation(to), to.translation(from));
+                for key in (b'a'..=b'z').map(|c| c as i32) {
+                    if let Some(moved) = there.get(key) {
+                        assert_eq!(back.get(moved), Some(key), "{} -> {} -> {}", from, to, from);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_layout_names() {
+        assert_eq!("AZERTY".parse::<Layout>(), Ok(Layout::Azerty));
+        assert!("colemak".parse::<Layout>().is_err());
+    }
+}
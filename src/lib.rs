@@ -0,0 +1,61 @@
+//! Reading, editing and sharing Battlebit Remastered's settings.
+//!
+//! The game keeps its settings in Unity's PlayerPrefs, i.e. the Windows registry, or a Wine/Proton prefix's
+//! `user.reg` on Linux. A [`Storage`] is where those raw values live, and a [`State`] decodes them into
+//! [`BattlebitType`]s that can be filtered, converted to and from TOML, changed and saved back.
+//!
+//! ```no_run
+//! use bbr_settings_editor::{storage, State};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut state = State::new(storage::open_default()?)?;
+//! println!("{}", state.to_toml());
+//!
+//! state.from_toml_str(&std::fs::read_to_string("settings.toml")?)?;
+//! state.save_registry()?;
+//! # Ok(())
+//! # }
+//! ```
+
+/// Timestamped snapshots of the settings, taken before anything is written.
+pub mod backup;
+/// The setting types and [`State`], which tracks changes to them.
+pub mod battlebit;
+/// Drawing the keybindings as a printable keyboard and mouse.
+pub mod cheatsheet;
+/// Parsing colors written as hex, `rgb()`, `hsl()` or by name.
+pub mod color;
+/// Comparing two sets of settings.
+pub mod diff;
+/// The errors reading a setting can fail with.
+pub mod error;
+/// Picking out groups of settings, e.g. the keybindings.
+pub mod filters;
+/// Reading settings out of an offline `NTUSER.DAT`.
+pub mod hive;
+/// Finding keys bound to more than one action and moving bindings to other keys.
+pub mod keybinds;
+/// Unity's `KeyCode` names.
+pub mod keycode;
+/// Keyboard layouts, for moving bindings between them.
+pub mod layout;
+/// Tables for moving the keybindings to the other hand.
+pub mod mirror;
+/// Regedit `.reg` files.
+pub mod regfile;
+/// Everywhere settings can be read from, as one type.
+pub mod source;
+/// Finding BattleBit's Proton prefixes in Steam's libraries.
+pub mod steam;
+/// Where the raw registry values are kept.
+pub mod storage;
+/// TOML configs.
+pub mod toml;
+/// Wine/Proton `user.reg` files.
+pub mod wine;
+
+pub use battlebit::{BattlebitType, State};
+pub use error::SettingsError;
+pub use filters::{Filter, FilterVariant};
+pub use source::Source;
+pub use storage::Storage;
@@ -1,128 +1,837 @@
-mod battlebit;
-mod filters;
-mod toml;
+use std::path::PathBuf;
 
-use clap::{command, Parser};
+use bbr_settings_editor::{
+    backup, battlebit, cheatsheet, color, diff, error, filters, hive, keybinds, layout::Layout,
+    mirror::{self, Mirror},
+    steam, storage, Filter, Source,
+};
+use clap::{Parser, Subcommand};
 
 macro_rules! prompt {
-    ($message:expr, $function:expr $(, $args:expr)*) => {{
-        use std::io::{stdin, stdout, Write};
+    ($confirm:expr, $message:expr, $function:expr $(, $args:expr)*) => {{
+        if confirm($confirm, &$message)? {
+            $function($($args),*)?;
+        } else {
+            println!("Operation canceled.");
+        }
+    }};
+}
 
-        loop {
-            print!("{} (Y/n): ", $message);
-            stdout().flush().expect("Failed to flush stdout");
+/// How prompts get answered.
+#[derive(Debug, Clone, Copy)]
+enum Confirm {
+    Ask,
+    Yes,
+    /// Nobody is there to answer, so anything that needs confirming fails instead.
+    Refuse,
+}
 
-            let mut input = String::new();
-            stdin().read_line(&mut input).expect("Failed to read input");
+fn confirm(mode: Confirm, message: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    use std::io::{stdin, stdout, Write};
 
-            let response = input.trim().to_lowercase();
+    match mode {
+        Confirm::Yes => return Ok(true),
+        Confirm::Refuse => {
+            return Err(fail(Failure::Refused)(format!(
+                "Can't ask \"{}\" without input, pass `--yes` to go ahead",
+                message
+            )))
+        }
+        Confirm::Ask => {}
+    }
 
-            match response.as_str() {
-                "" | "y" | "yes" => {
-                    $function($($args),*)?;
-                    break;
-                }
-                "n" | "no" => {
-                    println!("Operation canceled.");
-                    break;
-                }
-                _ => {
-                    println!("Invalid input. Please enter 'Y' or 'N'.");
-                }
-            }
+    loop {
+        print!("{} (Y/n): ", message);
+        stdout().flush()?;
+
+        let mut input = String::new();
+        // Stdin closing is taken as a no rather than an endless loop of empty answers.
+        if stdin().read_line(&mut input)? == 0 {
+            println!();
+            return Ok(false);
         }
-    }};
+
+        match input.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Invalid input. Please enter 'Y' or 'N'."),
+        }
+    }
+}
+
+/// The kinds of failure scripts can tell apart by exit code. Anything else exits with 1, and clap uses 2 for
+/// invalid arguments.
+#[derive(Debug, Clone, Copy)]
+enum Failure {
+    RegistryNotFound = 3,
+    Parse = 4,
+    Validation = 5,
+    Write = 6,
+    /// Something needed confirming, but there was nobody to ask.
+    Refused = 7,
+}
+
+#[derive(Debug)]
+struct Failed(Failure, Box<dyn std::error::Error>);
+
+impl std::fmt::Display for Failed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.1.fmt(f)
+    }
+}
+
+impl std::error::Error for Failed {}
+
+/// Tags an error with the kind of failure it is, for use with `map_err`.
+fn fail<E: Into<Box<dyn std::error::Error>>>(failure: Failure) -> impl FnOnce(E) -> Box<dyn std::error::Error> {
+    move |e| Box::new(Failed(failure, e.into()))
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[clap(short, long, help="The filepath of the TOML to import", conflicts_with = "output")]
-    input: Option<String>,
+    #[clap(short, long, global = true, help="The Wine/Proton prefix (or its `user.reg`) to use instead of the Windows registry")]
+    prefix: Option<PathBuf>,
+    #[clap(long, global = true, help="An offline `NTUSER.DAT` hive to read the settings from", conflicts_with = "prefix")]
+    hive: Option<PathBuf>,
+    #[clap(short, long, global = true, help="Answer yes to every prompt")]
+    yes: bool,
+    #[clap(long, global = true, help="Never prompt, failing instead of asking for confirmation. Implied when stdin isn't a terminal")]
+    no_input: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[clap(about="Export the settings to a TOML or `.reg` file, or draw the keybindings to an `.svg` or `.html` file")]
+    Export {
+        #[clap(help="The filepath to export to")]
+        output: String,
+        #[clap(short, long, help="Filters to include during an export")]
+        filters: Option<Vec<String>>,
+        #[clap(short = 'x', long, help="Filters to leave out of the export")]
+        exclude: Option<Vec<String>>,
+        #[clap(long, help="Export every value byte-for-byte, including ones that can't be decoded")]
+        lossless: bool,
+    },
+    #[clap(about="Import the settings from a TOML or `.reg` file")]
+    Import {
+        #[clap(help="The filepath to import from")]
+        input: String,
+        #[clap(long, help="Only show what would change, without writing anything")]
+        dry_run: bool,
+        #[clap(long, help="Refuse to import if it would bind a key to two actions at once")]
+        refuse_conflicts: bool,
+    },
+    #[clap(about="Print a single setting")]
+    Get {
+        #[clap(help="The name of the setting")]
+        name: String,
+    },
+    #[clap(about="Change a single setting")]
+    Set {
+        #[clap(help="The name of the setting")]
+        name: String,
+        #[clap(help="The new value")]
+        value: String,
+    },
+    #[clap(about="List the current settings")]
+    List {
+        #[clap(short, long, help="Filters to include")]
+        filters: Option<Vec<String>>,
+        #[clap(short = 'x', long, help="Filters to leave out")]
+        exclude: Option<Vec<String>>,
+    },
+    #[clap(about="Show how two sets of settings differ")]
+    Diff {
+        #[clap(help="What to compare from: a TOML, `.reg`, `user.reg` or `NTUSER.DAT` file, a prefix, `backup:<id>` or `live`")]
+        from: String,
+        #[clap(help="What to compare to, the same kinds as `from`. If left out, `from` is compared to the current settings")]
+        to: Option<String>,
+        #[clap(long, help="Print the differences as JSON")]
+        json: bool,
+    },
+    #[clap(about="Delete settings so the game recreates them with their defaults")]
+    Reset {
+        #[clap(short, long, help="Filters to reset, everything is reset if none are given")]
+        filters: Option<Vec<String>>,
+        #[clap(short = 'x', long, help="Filters to keep as they are")]
+        exclude: Option<Vec<String>>,
+    },
+    #[clap(about="Back up the current settings")]
+    Backup {
+        #[command(subcommand)]
+        action: Option<BackupCommand>,
+    },
+    #[clap(about="Restore the settings from a backup")]
+    Restore {
+        #[clap(help="The id of the backup, see `backup list`")]
+        id: String,
+        #[clap(long, help="Restore the backup even if it was taken from other settings, e.g. another prefix")]
+        any_source: bool,
+    },
+    #[clap(about="Check that a TOML or `.reg` file can be imported, without writing anything")]
+    Validate {
+        #[clap(help="The filepath to check")]
+        file: String,
+    },
+    #[clap(about="List every BattleBit Proton prefix that can be found")]
+    Prefixes,
+    #[clap(about="Work with the keybindings")]
+    Keybinds {
+        #[command(subcommand)]
+        action: KeybindsCommand,
+    },
+}
 
-    #[clap(short, long, help="The filepath to export the TOML to", conflicts_with = "input")]
-    output: Option<String>,
-    #[clap(short, long, help="Filters to include during an export", conflicts_with = "input")]
+impl Command {
+    /// Whether the command changes the live settings, which a read-only hive can't take.
+    fn writes_settings(&self) -> bool {
+        match self {
+            Command::Import { dry_run, .. } => !dry_run,
+            Command::Set { .. } | Command::Reset { .. } | Command::Restore { .. } => true,
+            Command::Keybinds {
+                action: KeybindsCommand::Remap { file, .. } | KeybindsCommand::Mirror { file, .. },
+            } => file.is_none(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupCommand {
+    #[clap(about="Back up the current settings (the default)")]
+    Create,
+    #[clap(about="List the backups taken so far")]
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum KeybindsCommand {
+    #[clap(about="Find keys bound to more than one action")]
+    Check {
+        #[clap(help="What to check, the same kinds as `diff` takes. The current settings if left out")]
+        source: Option<String>,
+    },
+    #[clap(about="Move the keybindings to the same physical keys on another keyboard layout")]
+    Remap {
+        #[clap(long, help="The layout the bindings were made on: qwerty, azerty, qwertz or dvorak")]
+        from: Layout,
+        #[clap(long, help="The layout to move them to")]
+        to: Layout,
+        #[clap(help="A TOML file to remap instead of the current settings")]
+        file: Option<String>,
+        #[clap(short, long, help="Where to write the remapped file, it is edited in place if left out")]
+        output: Option<String>,
+    },
+    #[clap(about="Move the keybindings to the other hand, e.g. WASD to IJKL")]
+    Mirror {
+        #[clap(long, default_value="ijkl", help="The mirror table: ijkl, arrows, numpad or a TOML file of `Key = \"OtherKey\"` entries")]
+        table: String,
+        #[clap(help="A TOML file to mirror instead of the current settings")]
+        file: Option<String>,
+        #[clap(short, long, help="Where to write the mirrored file, it is edited in place if left out")]
+        output: Option<String>,
+    },
+}
+
+/// Parses the `-f` filters along with the `--exclude` ones, where each expression is the same as `-f "!(expression)"`.
+fn parse_filter_args(
     filters: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+) -> Result<Option<Vec<filters::FilterVariant>>, Box<dyn std::error::Error>> {
+    let parse = |f: Option<Vec<String>>| filters::parse_filters(f.unwrap_or_default()).map_err(fail(Failure::Parse));
+
+    let mut filters = parse(filters)?;
+    filters.extend(parse(exclude)?.into_iter().map(|f| filters::FilterVariant::Not(Box::new(f))));
+
+    Ok((!filters.is_empty()).then_some(filters))
 }
 
-fn process_filters(filters: Vec<String>) -> Vec<String> {
-    filters
+fn open_storage(args: &Args) -> std::io::Result<Box<dyn storage::Storage>> {
+    if let Some(hive) = &args.hive {
+        return Ok(Box::new(hive::Hive::open(hive)?));
+    }
+
+    match &args.prefix {
+        Some(prefix) => storage::open_prefix(prefix),
+        None => storage::open_default(),
+    }
+}
+
+fn list_prefixes_cmd() {
+    let candidates = steam::find_prefixes();
+
+    if candidates.is_empty() {
+        tracing::warn!("No BattleBit Proton prefixes found");
+        return;
+    }
+
+    for candidate in candidates {
+        println!(
+            "{} ({}{})",
+            candidate.user_reg.display(),
+            candidate.install,
+            if candidate.installed { ", installed" } else { "" }
+        );
+    }
+}
+
+fn is_reg_file(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("reg"))
+}
+
+fn load_file(bbr: &mut battlebit::State, file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let result = if is_reg_file(file) {
+        let bytes = std::fs::read(file)?;
+        bbr.from_reg_file(&bytes)
+    } else {
+        let toml_str = std::fs::read_to_string(file)?;
+        bbr.from_toml_str(&toml_str)
+    };
+
+    result.map_err(|e| fail(failure(&e))(e.in_file(file)))
+}
+
+/// Whether a config error is in how the file is written, or in what it asks for.
+fn failure(e: &error::SettingsError) -> Failure {
+    use error::ErrorKind;
+
+    match &e.kind {
+        ErrorKind::Syntax(_) | ErrorKind::InvalidRegFile(_) | ErrorKind::InvalidFilter { .. } => Failure::Parse,
+        ErrorKind::InvalidValue { .. } | ErrorKind::UnknownType(_) | ErrorKind::Ambiguous { .. } => {
+            Failure::Validation
+        }
+        ErrorKind::Multiple(errors) => errors.first().map_or(Failure::Validation, failure),
+    }
+}
+
+fn save_with_backup(bbr: &mut battlebit::State) -> Result<(), Box<dyn std::error::Error>> {
+    let id = backup::create(bbr).map_err(fail(Failure::Write))?;
+    tracing::info!("Backed up the current settings as `{}`", id);
+    bbr.save_registry().map_err(fail(Failure::Write))?;
+    Ok(())
+}
+
+/// Prints a table of every setting that saving would touch and returns how many would actually change.
+fn print_changes(bbr: &battlebit::State) -> Result<usize, Box<dyn std::error::Error>> {
+    use ansi_term::{Colour, Style};
+    use battlebit::Change;
+    use std::io::IsTerminal;
+
+    let changes = bbr.pending_changes()?;
+
+    let rows: Vec<(Style, &str, &str, String, String)> = changes
         .iter()
-        .flat_map(|f| {
-            if f.contains(',') {
-                f.split(',').map(|s| s.to_string()).collect()
-            } else {
-                vec![f.to_string()]
-            }
+        .map(|c| {
+            let (style, marker, old, new) = match &c.change {
+                Change::Added(new) => (Colour::Green.normal(), "+", String::new(), new.to_display_string()),
+                Change::Changed(old, new) => (Colour::Yellow.normal(), "~", old.to_display_string(), new.to_display_string()),
+                Change::Unchanged(value) => (Style::new().dimmed(), "=", value.to_display_string(), value.to_display_string()),
+                Change::Deleted(old) => (Colour::Red.normal(), "-", old.to_display_string(), String::new()),
+            };
+            (style, marker, c.name.as_str(), old, new)
         })
-        .collect()
-}
+        .collect();
+
+    let name_width = rows.iter().map(|r| r.2.len()).max().unwrap_or(0).max("Setting".len());
+    let old_width = rows.iter().map(|r| r.3.len()).max().unwrap_or(0).max("Old".len());
 
-fn input_cmd(mut bbr: battlebit::State, args: Args) -> Result<(), Box<dyn std::error::Error>> {
-    let input = match args.input {
-        Some(file) => file,
-        None => {
-            return Err("No input file provided".into());
+    // Only colored on a terminal, see https://no-color.org.
+    let colored = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+
+    println!("  {:name_width$}  {:old_width$}     New", "Setting", "Old");
+    for (style, marker, name, old, new) in &rows {
+        let line = format!("{} {:name_width$}  {:old_width$}  →  {}", marker, name, old, new);
+        if colored {
+            println!("{}", style.paint(line));
+        } else {
+            println!("{}", line);
         }
-    };
+    }
+
+    let changed = changes
+        .iter()
+        .filter(|c| !matches!(c.change, Change::Unchanged(_)))
+        .count();
+    println!("{} to change, {} unchanged", changed, changes.len() - changed);
+
+    Ok(changed)
+}
 
-    let toml_str = std::fs::read_to_string(&input)?;
-    bbr.from_toml_str(&toml_str)?;
-    bbr.save_registry()?;
+fn import_cmd(mut bbr: battlebit::State, input: String) -> Result<(), Box<dyn std::error::Error>> {
+    save_with_backup(&mut bbr)?;
 
     tracing::info!("Successfully imported Battlebit configuration from `{}`", input);
 
     Ok(())
 }
 
-fn output_cmd(bbr: battlebit::State, args: Args) -> Result<(), Box<dyn std::error::Error>> {
-    let output = match args.output {
-        Some(file) => file,
-        None => {
-            return Err("No output file provided".into());
+fn export_cmd(
+    bbr: battlebit::State,
+    output: String,
+    filters: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    lossless: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let filters = parse_filter_args(filters, exclude)?;
+
+    if let Some(format) = cheatsheet::Format::from_path(&output) {
+        let settings = bbr
+            .registry
+            .iter()
+            .filter(|(name, value)| match &filters {
+                Some(filters) => filters.filter(name, value.to_reg_type_str()),
+                None => true,
+            })
+            .map(|(name, value)| (name.as_str(), value));
+        std::fs::write(&output, format.render(settings)).map_err(fail(Failure::Write))?;
+
+        tracing::info!("Successfully drew the keybindings to `{}`", output);
+        return Ok(());
+    }
+
+    let contents = match (is_reg_file(&output), filters) {
+        (true, Some(filters)) => bbr.to_filtered_reg(filters)?,
+        (true, None) => bbr.to_reg()?,
+        (false, filters) if lossless => bbr.to_lossless_toml(filters)?.into_bytes(),
+        (false, Some(filters)) => bbr.to_filtered_toml(filters).into_bytes(),
+        (false, None) => bbr.to_toml().into_bytes(),
+    };
+
+    std::fs::write(&output, contents).map_err(fail(Failure::Write))?;
+
+    tracing::info!("Successfully exported Battlebit configuration to `{}`", output);
+
+    Ok(())
+}
+
+fn find_setting<'a>(
+    bbr: &'a battlebit::State,
+    name: &str,
+) -> Result<&'a battlebit::BattlebitType, Box<dyn std::error::Error>> {
+    if let Some(value) = bbr.registry.get(name) {
+        return Ok(value);
+    }
+
+    let query = name.to_lowercase();
+    let mut similar: Vec<&String> = bbr
+        .registry
+        .keys()
+        .filter(|n| n.to_lowercase().contains(&query))
+        .collect();
+    similar.sort();
+
+    if similar.is_empty() {
+        return Err(format!("No setting named `{}`", name).into());
+    }
+
+    Err(format!(
+        "No setting named `{}`, did you mean: {}",
+        name,
+        similar
+            .iter()
+            .take(5)
+            .map(|n| format!("`{}`", n))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+    .into())
+}
+
+fn get_cmd(bbr: &battlebit::State, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(rgba) = bbr.color(name) {
+        println!("{} = {} (color)", name, color::to_hex(rgba));
+        return Ok(());
+    }
+
+    let value = find_setting(bbr, name)?;
+
+    println!("{} = {} ({})", name, value.to_toml_value(), value.to_reg_type_str());
+
+    Ok(())
+}
+
+fn set_cmd(
+    mut bbr: battlebit::State,
+    name: String,
+    value: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(old) = bbr.color(&name) {
+        let new = color::parse_color(&value)
+            .ok_or_else(|| error::SettingsError::invalid_value("a color", format!("`{}`", value)).for_setting(&name))
+            .map_err(fail(Failure::Validation))?;
+
+        bbr.update_color(&name, new).map_err(fail(Failure::Validation))?;
+        save_with_backup(&mut bbr)?;
+
+        tracing::info!("Successfully set `{}`: {} -> {}", name, color::to_hex(old), color::to_hex(new));
+        return Ok(());
+    }
+
+    let current = find_setting(&bbr, &name)?;
+    let old = current.to_toml_value();
+    let value = current
+        .parse_like(&value)
+        .map_err(|e| e.for_setting(&name))
+        .map_err(fail(Failure::Validation))?;
+    let new = value.to_toml_value();
+
+    bbr.update_registry(&name, value).map_err(fail(Failure::Validation))?;
+    save_with_backup(&mut bbr)?;
+
+    tracing::info!("Successfully set `{}`: {} -> {}", name, old, new);
+
+    Ok(())
+}
+
+fn list_cmd(
+    bbr: &battlebit::State,
+    filters: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let filters = parse_filter_args(filters, exclude)?;
+
+    let mut names: Vec<&String> = bbr.registry.keys().collect();
+    names.sort();
+
+    let mut colors = std::collections::HashSet::new();
+    for name in names {
+        let value = &bbr.registry[name];
+        let typ = value.to_reg_type_str();
+
+        if let Some(filters) = &filters {
+            if !filters.filter(name, typ) {
+                continue;
+            }
         }
+
+        // Colors are shown whole, once, rather than a line per channel.
+        if let battlebit::BattlebitType::Color(_) = value {
+            let color = color::color_name(name);
+            if let (true, Some(rgba)) = (colors.insert(color), bbr.color(color)) {
+                println!("{} = {} ({})", color, color::to_hex(rgba), typ);
+            }
+            continue;
+        }
+
+        println!("{} = {} ({})", name, value.to_toml_value(), typ);
+    }
+
+    Ok(())
+}
+
+fn diff_cmd(args: &Args, from: &str, to: Option<&str>, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    // With a single source, show what importing it would change.
+    let previews_import = to.is_none();
+    let (from, to) = match to {
+        Some(to) => (Source::parse(from), Source::parse(to)),
+        None => (Source::Live, Source::parse(from)),
     };
 
-    let filters = args
-        .filters
-        .as_ref()
-        .map(|f| process_filters(f.clone()))
-        .unwrap_or_default();
+    let live = || open_storage(args).map_err(fail(Failure::RegistryNotFound));
+    let old = from.load(live)?;
+    let new = to.load(live)?;
+    let mut differences = diff::diff(old, new);
 
-    let toml = if filters.len() > 0 {
-        bbr.to_filtered_toml(filters::parse_filters(filters))
+    // Importing never deletes anything, so settings the file leaves out stay as they are.
+    if previews_import {
+        differences.retain(|(_, difference)| !matches!(difference, diff::Difference::Removed(_)));
+    }
+
+    if json {
+        println!("{}", diff::to_json(&differences)?);
+    } else if differences.is_empty() {
+        tracing::info!("No differences between {} and {}", from, to);
     } else {
-        bbr.to_toml()
-    };
+        diff::print(&differences);
+    }
+
+    Ok(())
+}
 
-    std::fs::write(&output, toml)?;
+fn reset_cmd(mut bbr: battlebit::State, count: usize) -> Result<(), Box<dyn std::error::Error>> {
+    save_with_backup(&mut bbr)?;
 
-    tracing::info!("Successfully exported Battlebit configuration to `{}`", output);
+    tracing::info!("Successfully reset {} settings", count);
+
+    Ok(())
+}
+
+fn validate_cmd(mut bbr: battlebit::State, file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    load_file(&mut bbr, file)?;
+
+    tracing::info!(
+        "`{}` is valid, importing it would write {} settings",
+        file,
+        bbr.updated_registry.len()
+    );
+
+    Ok(())
+}
+
+fn keybinds_check_cmd(args: &Args, source: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let source = source.map_or(Source::Live, Source::parse);
+    let settings = source.load(|| open_storage(args).map_err(fail(Failure::RegistryNotFound)))?;
+    let conflicts = keybinds::conflicts(settings.iter().map(|(name, value)| (name.as_str(), value)));
+
+    if conflicts.is_empty() {
+        tracing::info!("No conflicting keybindings in {}", source);
+        return Ok(());
+    }
+
+    for conflict in &conflicts {
+        println!("{}", conflict);
+    }
+
+    Err(fail(Failure::Validation)(format!(
+        "Found {} conflicting keybindings in {}",
+        conflicts.len(),
+        source
+    )))
+}
+
+/// The key map a `keybinds` transform moves bindings with, and how to describe it.
+fn key_map(action: &KeybindsCommand) -> Result<(keybinds::KeyMap, String), Box<dyn std::error::Error>> {
+    match action {
+        KeybindsCommand::Remap { from, to, .. } => Ok((from.translation(*to), format!("from {} to {}", from, to))),
+        KeybindsCommand::Mirror { table, .. } => match table.parse::<Mirror>() {
+            Ok(table) => Ok((table.key_map(), format!("with the {} mirror table", table))),
+            Err(_) if std::path::Path::new(table).is_file() => {
+                let contents = std::fs::read_to_string(table)?;
+                let map = mirror::from_toml(&contents)
+                    .map_err(|e| e.in_file(table))
+                    .map_err(fail(Failure::Parse))?;
+                Ok((map, format!("with mirror table `{}`", table)))
+            }
+            Err(_) => Err(format!("No mirror table `{}`, expected ijkl, arrows, numpad or a TOML file", table).into()),
+        },
+        KeybindsCommand::Check { .. } => unreachable!("Checking doesn't move any bindings"),
+    }
+}
+
+fn report_unmapped(unmapped: &[keybinds::Unmapped], description: &str) {
+    for binding in unmapped {
+        tracing::warn!("Couldn't move {} {}, it was left as it was", binding, description);
+    }
+}
+
+fn remap_file_cmd(
+    file: String,
+    output: String,
+    map: keybinds::KeyMap,
+    description: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = std::fs::read_to_string(&file)?;
+    let (config, unmapped) = keybinds::remap_toml(&config, &map)
+        .map_err(|e| e.in_file(&file))
+        .map_err(fail(Failure::Parse))?;
+    report_unmapped(&unmapped, &description);
+
+    std::fs::write(&output, config).map_err(fail(Failure::Write))?;
+
+    tracing::info!("Successfully moved the keybindings in `{}` {} into `{}`", file, description, output);
+
+    Ok(())
+}
+
+fn remap_cmd(mut bbr: battlebit::State, description: String) -> Result<(), Box<dyn std::error::Error>> {
+    save_with_backup(&mut bbr)?;
+
+    tracing::info!("Successfully moved the keybindings {}", description);
+
+    Ok(())
+}
+
+fn list_backups_cmd() -> Result<(), Box<dyn std::error::Error>> {
+    let backups = backup::list()?;
+
+    if backups.is_empty() {
+        tracing::warn!("No backups found in `{}`", backup::backups_dir()?.display());
+        return Ok(());
+    }
+
+    for backup in backups {
+        let source = backup.source.as_deref().unwrap_or("unknown");
+        println!("{} ({} values from {}) {}", backup.id, backup.values, source, backup.path.display());
+    }
 
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn restore_cmd(mut bbr: battlebit::State, id: String) -> Result<(), Box<dyn std::error::Error>> {
+    save_with_backup(&mut bbr)?;
+
+    tracing::info!("Successfully restored Battlebit configuration from backup `{}`", id);
+
+    Ok(())
+}
+
+fn main() -> std::process::ExitCode {
+    #[cfg(windows)]
     ansi_term::enable_ansi_support().unwrap();
     tracing_subscriber::fmt::init();
 
-    let args = Args::parse();
-    let bbr = match battlebit::State::new() {
-        Ok(bbr) => bbr,
+    match run(Args::parse()) {
+        Ok(()) => std::process::ExitCode::SUCCESS,
         Err(e) => {
-            tracing::error!("Failed to access Battlebit configuration: {}", e);
-            return Ok(());
+            tracing::error!("{}", e);
+            let code = match e.downcast_ref::<Failed>() {
+                Some(Failed(failure, _)) => *failure as u8,
+                None => 1,
+            };
+            std::process::ExitCode::from(code)
         }
-    };
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::IsTerminal;
 
-    if args.input.is_some() {
-        prompt!(format!("Are you sure you want to import from `{}`?", args.input.clone().unwrap()), input_cmd, bbr, args);
-    } else if args.output.is_some() {
-        prompt!(format!("Are you sure you want to export to `{}`?", args.output.clone().unwrap()), output_cmd, bbr, args);
+    let confirm = if args.yes {
+        Confirm::Yes
+    } else if args.no_input || !std::io::stdin().is_terminal() {
+        Confirm::Refuse
     } else {
-        tracing::warn!("No command provided");
+        Confirm::Ask
+    };
+
+    match &args.command {
+        Command::Prefixes => {
+            list_prefixes_cmd();
+            return Ok(());
+        }
+        Command::Backup {
+            action: Some(BackupCommand::List),
+        } => return list_backups_cmd(),
+        Command::Diff { from, to, json } => return diff_cmd(&args, from, to.as_deref(), *json),
+        Command::Keybinds {
+            action: KeybindsCommand::Check { source },
+        } => return keybinds_check_cmd(&args, source.as_deref()),
+        Command::Keybinds {
+            action:
+                action @ (KeybindsCommand::Remap {
+                    file: Some(file),
+                    output,
+                    ..
+                }
+                | KeybindsCommand::Mirror {
+                    file: Some(file),
+                    output,
+                    ..
+                }),
+        } => {
+            let (map, description) = key_map(action)?;
+            let output = output.as_ref().unwrap_or(file);
+            prompt!(confirm, format!("Are you sure you want to write `{}`?", output), remap_file_cmd, file.clone(), output.clone(), map, description);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // Checked before anything is backed up, as saving would only fail at the very end.
+    if args.hive.is_some() && args.command.writes_settings() {
+        return Err(fail(Failure::Write)(
+            "Offline hives are read-only, export the settings and import them with `--prefix` or on Windows instead",
+        ));
+    }
+
+    let mut bbr = open_storage(&args)
+        .and_then(battlebit::State::new)
+        .map_err(|e| format!("Failed to access Battlebit configuration: {}", e))
+        .map_err(fail(Failure::RegistryNotFound))?;
+
+    match args.command {
+        Command::Export {
+            output,
+            filters,
+            exclude,
+            lossless,
+        } => {
+            // Exporting doesn't change any settings, so there is nothing to confirm.
+            export_cmd(bbr, output, filters, exclude, lossless)?;
+        }
+        Command::Import {
+            input,
+            dry_run,
+            refuse_conflicts,
+        } => {
+            load_file(&mut bbr, &input)?;
+            if print_changes(&bbr)? == 0 {
+                tracing::info!("`{}` matches the current settings, nothing to import", input);
+                return Ok(());
+            }
+
+            let conflicts = keybinds::introduced_conflicts(&bbr);
+            for conflict in &conflicts {
+                tracing::warn!("Importing `{}` would bind {}", input, conflict);
+            }
+            if refuse_conflicts && !conflicts.is_empty() {
+                return Err(fail(Failure::Validation)(format!(
+                    "`{}` introduces {} conflicting keybindings",
+                    input,
+                    conflicts.len()
+                )));
+            }
+            if dry_run {
+                return Ok(());
+            }
+
+            prompt!(confirm, format!("Are you sure you want to import from `{}`?", input), import_cmd, bbr, input.clone());
+        }
+        Command::Get { name } => get_cmd(&bbr, &name)?,
+        Command::Set { name, value } => {
+            prompt!(confirm, format!("Are you sure you want to set `{}` to `{}`?", name, value), set_cmd, bbr, name.clone(), value.clone());
+        }
+        Command::List { filters, exclude } => list_cmd(&bbr, filters, exclude)?,
+        Command::Reset { filters, exclude } => {
+            let filters = parse_filter_args(filters, exclude)?;
+            let count = bbr.reset(filters.as_deref());
+            if print_changes(&bbr)? == 0 {
+                tracing::info!("No settings to reset");
+                return Ok(());
+            }
+
+            prompt!(confirm, "Are you sure you want to reset the settings to their defaults?", reset_cmd, bbr, count);
+        }
+        Command::Backup { .. } => {
+            let id = backup::create(&bbr).map_err(fail(Failure::Write))?;
+            tracing::info!("Backed up the current settings as `{}`", id);
+        }
+        Command::Restore { id, any_source } => {
+            backup::restore(&mut bbr, &id, any_source).map_err(|e| match e.downcast::<backup::OtherSource>() {
+                Ok(e) => fail(Failure::Validation)(format!("{}, pass `--any-source` to restore it anyway", e)),
+                Err(e) => e,
+            })?;
+            if print_changes(&bbr)? == 0 {
+                tracing::info!("The current settings already match backup `{}`", id);
+                return Ok(());
+            }
+
+            prompt!(confirm, format!("Are you sure you want to restore backup `{}`?", id), restore_cmd, bbr, id.clone());
+        }
+        Command::Validate { file } => validate_cmd(bbr, &file)?,
+        Command::Keybinds { action } => {
+            let (map, description) = key_map(&action)?;
+            let unmapped = keybinds::remap(&mut bbr, &map).map_err(fail(Failure::Validation))?;
+            report_unmapped(&unmapped, &description);
+            if print_changes(&bbr)? == 0 {
+                tracing::info!("No keybindings move {}", description);
+                return Ok(());
+            }
+            for conflict in keybinds::introduced_conflicts(&bbr) {
+                tracing::warn!("Moving the keybindings would bind {}", conflict);
+            }
+
+            prompt!(confirm, format!("Are you sure you want to move the keybindings {}?", description), remap_cmd, bbr, description.clone());
+        }
+        Command::Prefixes | Command::Diff { .. } => unreachable!("Handled before loading the settings"),
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::{
+    battlebit::Key,
+    error::{ErrorKind, Location, SettingsError},
+    keybinds::KeyMap,
+    keycode,
+};
+
+/// A built-in table for moving the keybindings from the left hand to the right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirror {
+    /// WASD to IJKL, with the rest of the left half shifted over to match.
+    Ijkl,
+    /// WASD to the arrows, with the keys above them for Q, E, R, F, G and T.
+    Arrows,
+    /// WASD to 8456 on the keypad, with the keys around them for the rest.
+    Numpad,
+}
+
+/// The keys under the left hand, which are reported if a table has nowhere to put them.
+const LEFT_HAND: &[&str] = &[
+    "BackQuote", "Alpha1", "Alpha2", "Alpha3", "Alpha4", "Alpha5", "Tab", "Q", "W", "E", "R", "T", "CapsLock", "A",
+    "S", "D", "F", "G", "LeftShift", "Z", "X", "C", "V", "B", "LeftControl", "LeftAlt",
+];
+
+const MODIFIERS: &[(&str, &str)] = &[
+    ("LeftShift", "RightShift"),
+    ("LeftControl", "RightControl"),
+    ("LeftAlt", "RightAlt"),
+];
+
+const IJKL: &[(&str, &str)] = &[
+    ("Alpha1", "Alpha7"),
+    ("Alpha2", "Alpha8"),
+    ("Alpha3", "Alpha9"),
+    ("Alpha4", "Alpha0"),
+    ("Alpha5", "Minus"),
+    ("Q", "U"),
+    ("W", "I"),
+    ("E", "O"),
+    ("R", "P"),
+    ("T", "LeftBracket"),
+    ("A", "J"),
+    ("S", "K"),
+    ("D", "L"),
+    ("F", "Semicolon"),
+    ("G", "Quote"),
+    ("Z", "M"),
+    ("X", "Comma"),
+    ("C", "Period"),
+    ("V", "Slash"),
+];
+
+const ARROWS: &[(&str, &str)] = &[
+    ("W", "UpArrow"),
+    ("A", "LeftArrow"),
+    ("S", "DownArrow"),
+    ("D", "RightArrow"),
+    ("Q", "Delete"),
+    ("E", "PageDown"),
+    ("R", "PageUp"),
+    ("F", "End"),
+    ("G", "Home"),
+    ("T", "Insert"),
+];
+
+const NUMPAD: &[(&str, &str)] = &[
+    ("W", "Keypad8"),
+    ("A", "Keypad4"),
+    ("S", "Keypad5"),
+    ("D", "Keypad6"),
+    ("Q", "Keypad7"),
+    ("E", "Keypad9"),
+    ("Z", "Keypad1"),
+    ("X", "Keypad2"),
+    ("C", "Keypad3"),
+    ("R", "KeypadMinus"),
+    ("F", "KeypadPlus"),
+    ("G", "KeypadEnter"),
+    ("T", "KeypadMultiply"),
+    ("V", "KeypadPeriod"),
+];
+
+fn code(name: &str) -> i32 {
+    keycode::from_name(name).expect("Mirror tables only use known keys")
+}
+
+impl Mirror {
+    pub const ALL: [Mirror; 3] = [Mirror::Ijkl, Mirror::Arrows, Mirror::Numpad];
+
+    fn pairs(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Mirror::Ijkl => IJKL,
+            Mirror::Arrows => ARROWS,
+            Mirror::Numpad => NUMPAD,
+        }
+    }
+
+    /// Swaps each left-hand key with its right-hand twin, so nothing already on the right ends up sharing a
+    /// key. Left-hand keys without a twin have nowhere to go.
+    pub fn key_map(&self) -> KeyMap {
+        let mut map = KeyMap::default();
+
+        for name in LEFT_HAND {
+            map.insert(code(name), None);
+        }
+        for (left, right) in self.pairs().iter().chain(MODIFIERS) {
+            map.insert(code(left), Some(code(right)));
+            map.insert(code(right), Some(code(left)));
+        }
+
+        map
+    }
+}
+
+impl fmt::Display for Mirror {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mirror::Ijkl => write!(f, "ijkl"),
+            Mirror::Arrows => write!(f, "arrows"),
+            Mirror::Numpad => write!(f, "numpad"),
+        }
+    }
+}
+
+impl FromStr for Mirror {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Mirror::ALL
+            .into_iter()
+            .find(|mirror| mirror.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown mirror table `{}`, expected one of ijkl, arrows or numpad", s))
+    }
+}
+
+/// Reads a mirror table from TOML, where each entry moves the key it is named after, e.g. `W = "UpArrow"`.
+///
+/// Keys only move one way, so swapping two takes an entry for each. An empty string marks a key as having
+/// nowhere to go.
+pub fn from_toml(table: &str) -> Result<KeyMap, SettingsError> {
+    let entries: BTreeMap<String, String> = toml::from_str(table).map_err(|e| {
+        let location = e.span().map(|span| Location::at_offset(table, span.start));
+        SettingsError::new(ErrorKind::Syntax(e.message().to_string())).at(location)
+    })?;
+
+    let mut map = KeyMap::default();
+    for (from, to) in entries {
+        let fail = |e: SettingsError| e.for_setting(&from);
+        let to = match to.as_str() {
+            "" => None,
+            to => Some(Key::from_name(to).map_err(fail)?.0),
+        };
+        map.insert(Key::from_name(&from).map_err(fail)?.0, to);
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybinds::remap_toml;
+
+    #[test]
+    fn mirroring_twice_changes_nothing() {
+        for mirror in Mirror::ALL {
+            let map = mirror.key_map();
+
+            for &(_, key) in keycode::KEY_CODES.iter() {
+                if let Some(mirrored) = map.get(key) {
+                    assert_eq!(map.get(mirrored), Some(key), "{} with {}", Key(key).name(), mirror);
+                }
+            }
+        }
+
+        let config = r#"
+[Jump_key]
+typ = "key"
+value = "Space"
+
+[Forward_key]
+typ = "key"
+value = "W"
+
+[Reload_key]
+typ = "key"
+value = "R"
+"#;
+        let map = Mirror::Ijkl.key_map();
+        let (mirrored, _) = remap_toml(config, &map).unwrap();
+        assert_ne!(mirrored, config);
+        assert_eq!(remap_toml(&mirrored, &map).unwrap().0, config);
+    }
+
+    #[test]
+    fn left_hand_keys_without_a_twin_are_unmapped() {
+        let map = Mirror::Arrows.key_map();
+
+        assert_eq!(map.get(code("W")), Some(code("UpArrow")));
+        assert_eq!(map.get(code("UpArrow")), Some(code("W")));
+        assert_eq!(map.get(code("LeftShift")), Some(code("RightShift")));
+        assert_eq!(map.get(code("Z")), None);
+        assert_eq!(map.get(code("Mouse0")), Some(code("Mouse0")));
+    }
+
+    #[test]
+    fn reads_custom_tables() {
+        let map = from_toml("W = \"UpArrow\"\nZ = \"\"\n").unwrap();
+
+        assert_eq!(map.get(code("W")), Some(code("UpArrow")));
+        assert_eq!(map.get(code("UpArrow")), Some(code("UpArrow")));
+        assert_eq!(map.get(code("Z")), None);
+
+        let error = from_toml("W = \"UpArrow\"\nQ = \"Nowhere\"\n").unwrap_err();
+        assert_eq!(error.setting.as_deref(), Some("Q"));
+    }
+}
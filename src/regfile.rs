@@ -0,0 +1,340 @@
+use crate::{
+    battlebit::{BattlebitType, Raw, RegType, RegValue, State, BBR_REG_SUBKEY},
+    error::{ErrorKind, SettingsError},
+    filters::{Filter, FilterVariant},
+    wine,
+};
+
+const REGEDIT5_HEADER: &str = "Windows Registry Editor Version 5.00";
+const REGEDIT4_HEADER: &str = "REGEDIT4";
+
+/// Parses a regedit `.reg` file, returning the values found under the Battlebit key.
+///
+/// Both the UTF-16LE `Windows Registry Editor Version 5.00` format and the ANSI `REGEDIT4` format are accepted.
+pub fn parse_reg(bytes: &[u8]) -> Result<Vec<(String, RegValue)>, SettingsError> {
+    let invalid = |message: String| SettingsError::new(ErrorKind::InvalidRegFile(message));
+
+    let text = decode_reg(bytes).map_err(invalid)?;
+    let mut lines = logical_lines(&text).into_iter();
+
+    match lines.next().as_deref() {
+        Some(REGEDIT5_HEADER) | Some(REGEDIT4_HEADER) => {}
+        _ => return Err(invalid("Not a registry file, missing the `Windows Registry Editor` header".to_string())),
+    }
+
+    let mut values = Vec::new();
+    let mut in_bbr_key = false;
+
+    for line in lines {
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(key) = line.strip_prefix('[') {
+            let key = key.trim_end_matches(']');
+            in_bbr_key = is_bbr_key(key);
+            continue;
+        }
+
+        if !in_bbr_key {
+            continue;
+        }
+
+        let (name, data) = match wine::parse_quoted(&line) {
+            Some((name, rest)) => (name, rest.trim_start().strip_prefix('=')),
+            None => continue,
+        };
+        let data = match data {
+            Some(d) => d.trim_start(),
+            None => return Err(invalid(format!("Invalid registry value line: {}", line))),
+        };
+
+        // `"name"=-` deletes the value, which there is nothing to import for.
+        if data == "-" {
+            continue;
+        }
+
+        let value = wine::parse_data(data)
+            .ok_or_else(|| invalid("Invalid registry value data".to_string()).for_setting(&name))?;
+        values.push((name, value));
+    }
+
+    Ok(values)
+}
+
+fn decode_reg(bytes: &[u8]) -> Result<String, String> {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        return String::from_utf16(&units).map_err(|e| e.to_string());
+    }
+
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8(utf8.to_vec()).map_err(|e| e.to_string());
+    }
+
+    // REGEDIT4 files are written in the ANSI code page, which agrees with Latin-1 for everything we care about.
+    Ok(bytes.iter().map(|&b| b as char).collect())
+}
+
+/// Splits `text` into lines, joining any that end with a `\` continuation.
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for line in text.lines() {
+        let line = if current.is_empty() {
+            line.trim_end()
+        } else {
+            line.trim()
+        };
+
+        match line.strip_suffix('\\') {
+            Some(continued) => current.push_str(continued),
+            None => {
+                current.push_str(line);
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
+fn is_bbr_key(key: &str) -> bool {
+    let path = match key.split_once('\\') {
+        Some((hive, path))
+            if hive.eq_ignore_ascii_case("HKEY_CURRENT_USER") || hive.eq_ignore_ascii_case("HKCU") =>
+        {
+            path
+        }
+        _ => return false,
+    };
+
+    path.eq_ignore_ascii_case(BBR_REG_SUBKEY)
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn format_value(name: &str, value: &RegValue) -> String {
+    let name = quote(name);
+
+    match value.vtype {
+        RegType::REG_DWORD if value.bytes.len() == 4 => {
+            let dword = u32::from_le_bytes(value.bytes[..4].try_into().unwrap());
+            format!("{}=dword:{:08x}", name, dword)
+        }
+        RegType::REG_SZ => match wine::decode_utf16(&value.bytes) {
+            Some(s) => format!("{}={}", name, quote(&s)),
+            None => wine::format_hex(&name, value),
+        },
+        _ => wine::format_hex(&name, value),
+    }
+}
+
+impl State {
+    /// Exports every registry value as a UTF-16 regedit file.
+    pub fn to_reg(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.to_reg_with(|_, _| true)
+    }
+
+    /// Exports the registry values whose settings match `include` as a UTF-16 regedit file.
+    pub fn to_filtered_reg(
+        &self,
+        include: Vec<FilterVariant>,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.to_reg_with(|name, typ| include.filter(name, typ))
+    }
+
+    fn to_reg_with(
+        &self,
+        include: impl Fn(&str, &str) -> bool,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut lines = vec![
+            REGEDIT5_HEADER.to_string(),
+            String::new(),
+            format!("[HKEY_CURRENT_USER\\{}]", BBR_REG_SUBKEY),
+        ];
+
+        let mut values = self.storage.values()?;
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (name, value) in values {
+            if name.is_empty() {
+                continue;
+            }
+
+            // Filters work on the decoded setting, but the raw value is written so nothing gets lost.
+            let (logical, typ) = BattlebitType::from_reg(name.clone(), value.clone())?;
+            if !include(&logical, typ.to_reg_type_str()) {
+                continue;
+            }
+
+            lines.push(format_value(&name, &value));
+        }
+
+        lines.push(String::new());
+        let text = lines.join("\r\n").replace("\\\n", "\\\r\n") + "\r\n";
+
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(|c| c.to_le_bytes()));
+        Ok(bytes)
+    }
+
+    /// Queues every value in a regedit file to be written by [`State::save_registry`].
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_reg_file(&mut self, bytes: &[u8]) -> Result<(), SettingsError> {
+        for (name, value) in parse_reg(bytes)? {
+            // `.reg` files carry the exact value names and types, so they are written back byte-for-byte.
+            self.updated_registry.insert(name, BattlebitType::Raw(Raw(value)));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battlebit::{Change, Key},
+        storage::MemoryStorage,
+    };
+
+    fn utf16_file(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(|c| c.to_le_bytes()));
+        bytes
+    }
+
+    fn values() -> Vec<(String, RegValue)> {
+        vec![
+            (
+                "Jump_key_h123".to_string(),
+                RegValue {
+                    bytes: 32i32.to_le_bytes().to_vec(),
+                    vtype: RegType::REG_DWORD,
+                },
+            ),
+            (
+                "MasterVolume_float_h717095934".to_string(),
+                RegValue {
+                    bytes: 0.5f64.to_le_bytes().to_vec(),
+                    vtype: RegType::REG_DWORD,
+                },
+            ),
+            (
+                "PlayerName_h99".to_string(),
+                RegValue {
+                    bytes: (0..40).collect(),
+                    vtype: RegType::REG_BINARY,
+                },
+            ),
+            (
+                "unity.cloud_userid_h1".to_string(),
+                RegValue {
+                    bytes: wine::encode_utf16("a\"b\\c"),
+                    vtype: RegType::REG_SZ,
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let state = State::new(Box::new(MemoryStorage::new(values()))).unwrap();
+
+        assert_eq!(parse_reg(&state.to_reg().unwrap()).unwrap(), values());
+    }
+
+    #[test]
+    fn import_keeps_the_registry_types() {
+        let mut state = State::new(Box::new(MemoryStorage::default())).unwrap();
+        let file = utf16_file(concat!(
+            "Windows Registry Editor Version 5.00\r\n\r\n",
+            "[HKEY_CURRENT_USER\\SOFTWARE\\BattleBitDevTeam\\BattleBit]\r\n",
+            "\"UnityGraphicsQuality_h1669003810\"=dword:00000002\r\n",
+            "\"unity.cloud_userid_h1\"=\"abc\"\r\n",
+        ));
+
+        state.from_reg_file(&file).unwrap();
+        state.save_registry().unwrap();
+
+        let mut stored = state.storage.values().unwrap();
+        stored.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            stored,
+            vec![
+                (
+                    "UnityGraphicsQuality_h1669003810".to_string(),
+                    RegValue {
+                        bytes: vec![2, 0, 0, 0],
+                        vtype: RegType::REG_DWORD,
+                    },
+                ),
+                (
+                    "unity.cloud_userid_h1".to_string(),
+                    RegValue {
+                        bytes: wine::encode_utf16("abc"),
+                        vtype: RegType::REG_SZ,
+                    },
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_are_previewed_as_settings() {
+        let mut state = State::new(Box::new(MemoryStorage::default())).unwrap();
+        let file = utf16_file(concat!(
+            "Windows Registry Editor Version 5.00\r\n\r\n",
+            "[HKEY_CURRENT_USER\\SOFTWARE\\BattleBitDevTeam\\BattleBit]\r\n",
+            "\"Jump_key_h123\"=dword:00000020\r\n",
+        ));
+
+        state.from_reg_file(&file).unwrap();
+        let changes = state.pending_changes().unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(&changes[0].change, Change::Added(BattlebitType::Key(Key(32)))), "{:?}", changes);
+    }
+
+    #[test]
+    fn parses_regedit4_with_continuations() {
+        let file = concat!(
+            "REGEDIT4\r\n\r\n",
+            "[HKEY_CURRENT_USER\\Software\\Other]\r\n",
+            "\"Ignored\"=dword:00000001\r\n\r\n",
+            "[HKCU\\Software\\BattleBitDevTeam\\BattleBit]\r\n",
+            "; A comment\r\n",
+            "\"Big_h1\"=hex:00,01,\\\r\n",
+            "  02,03\r\n",
+            "\"Deleted_h2\"=-\r\n",
+        );
+
+        assert_eq!(
+            parse_reg(file.as_bytes()).unwrap(),
+            vec![(
+                "Big_h1".to_string(),
+                RegValue {
+                    bytes: vec![0, 1, 2, 3],
+                    vtype: RegType::REG_BINARY,
+                },
+            )]
+        );
+    }
+
+    #[test]
+    fn rejects_files_without_a_header() {
+        let error = parse_reg(b"[HKCU\\Software]\r\n").unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::InvalidRegFile(_)), "{}", error);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    backup,
+    battlebit::{BattlebitType, State},
+    hive, regfile,
+    storage::{MemoryStorage, Storage},
+    toml, wine,
+};
+
+/// Anywhere settings can be read from to compare them.
+pub enum Source {
+    /// The settings the game is currently using.
+    Live,
+    Backup(String),
+    Toml(PathBuf),
+    RegFile(PathBuf),
+    UserReg(PathBuf),
+    Hive(PathBuf),
+}
+
+impl Source {
+    /// Works out what kind of source `source` is, by name for `live` and `backup:<id>` and by contents for files.
+    pub fn parse(source: &str) -> Self {
+        if source.eq_ignore_ascii_case("live") {
+            return Source::Live;
+        }
+        if let Some(id) = source.strip_prefix("backup:") {
+            return Source::Backup(id.to_string());
+        }
+
+        let path = PathBuf::from(source);
+        if path.is_dir() {
+            return Source::UserReg(path.join("user.reg"));
+        }
+
+        let mut magic = [0u8; 16];
+        let read = std::fs::File::open(&path)
+            .and_then(|mut f| std::io::Read::read(&mut f, &mut magic))
+            .unwrap_or(0);
+        let magic = &magic[..read];
+
+        if magic.starts_with(b"regf") {
+            Source::Hive(path)
+        } else if magic.starts_with(b"WINE REGISTRY") {
+            Source::UserReg(path)
+        } else if has_extension(&path, "reg") {
+            Source::RegFile(path)
+        } else {
+            Source::Toml(path)
+        }
+    }
+
+    /// Reads every setting by name. `live` opens the current settings, and is only called for [`Source::Live`].
+    pub fn load(
+        &self,
+        live: impl FnOnce() -> Result<Box<dyn Storage>, Box<dyn std::error::Error>>,
+    ) -> Result<HashMap<String, BattlebitType>, Box<dyn std::error::Error>> {
+        let storage: Box<dyn Storage> = match self {
+            Source::Live => live()?,
+            Source::Backup(id) => {
+                let path = backup::snapshot_path(id)?;
+                let snapshot = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read backup `{}`: {}", id, e))?;
+                return Ok(toml::settings_from_toml(&snapshot).map_err(|e| e.in_file(path))?);
+            }
+            Source::Toml(path) => {
+                let toml = std::fs::read_to_string(path)?;
+                return Ok(toml::settings_from_toml(&toml).map_err(|e| e.in_file(path))?);
+            }
+            Source::RegFile(path) => Box::new(MemoryStorage::new(regfile::parse_reg(&std::fs::read(path)?)?)),
+            Source::UserReg(path) => Box::new(wine::UserReg::open(path)?),
+            Source::Hive(path) => Box::new(hive::Hive::open(path)?),
+        };
+
+        Ok(State::new(storage)?.registry)
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Live => write!(f, "the current settings"),
+            Source::Backup(id) => write!(f, "backup `{}`", id),
+            Source::Toml(path) | Source::RegFile(path) | Source::UserReg(path) | Source::Hive(path) => {
+                write!(f, "`{}`", path.display())
+            }
+        }
+    }
+}
+
+fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
@@ -0,0 +1,358 @@
+use std::path::{Path, PathBuf};
+
+/// BattleBit Remastered's Steam app id.
+pub const BBR_APP_ID: u32 = 671860;
+
+/// How Steam is installed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Install {
+    Native,
+    Flatpak,
+}
+
+impl std::fmt::Display for Install {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Install::Native => write!(f, "native"),
+            Install::Flatpak => write!(f, "flatpak"),
+        }
+    }
+}
+
+/// A Proton prefix that may hold BattleBit's settings.
+#[derive(Debug)]
+pub struct Candidate {
+    pub install: Install,
+    pub user_reg: PathBuf,
+    /// Whether the library also has an `appmanifest_<appid>.acf`, i.e. the game is installed there.
+    pub installed: bool,
+}
+
+/// A node of Valve's KeyValues (`.vdf`) format.
+#[derive(Debug)]
+pub enum Vdf {
+    Str(String),
+    Map(Vec<(String, Vdf)>),
+}
+
+impl Vdf {
+    pub fn get(&self, key: &str) -> Option<&Vdf> {
+        match self {
+            Vdf::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            Vdf::Str(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Vdf::Str(s) => Some(s),
+            Vdf::Map(_) => None,
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, Vdf)] {
+        match self {
+            Vdf::Map(entries) => entries,
+            Vdf::Str(_) => &[],
+        }
+    }
+}
+
+/// Parses Valve's KeyValues text format, as used by `libraryfolders.vdf` and `appmanifest_*.acf`.
+pub fn parse_vdf(text: &str) -> Option<Vdf> {
+    let mut tokens = tokenize_vdf(text).into_iter();
+    let entries = parse_vdf_map(&mut tokens, false)?;
+    Some(Vdf::Map(entries))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Str(String),
+    Open,
+    Close,
+}
+
+fn tokenize_vdf(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '"' => {
+                let mut s = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(c) => s.push(c),
+                            None => break,
+                        },
+                        c => s.push(c),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut s = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Str(s));
+            }
+        }
+    }
+
+    tokens
+}
+
+fn parse_vdf_map(
+    tokens: &mut impl Iterator<Item = Token>,
+    nested: bool,
+) -> Option<Vec<(String, Vdf)>> {
+    let mut entries = Vec::new();
+
+    loop {
+        let key = match tokens.next() {
+            Some(Token::Str(key)) => key,
+            Some(Token::Close) if nested => return Some(entries),
+            None if !nested => return Some(entries),
+            _ => return None,
+        };
+
+        let value = match tokens.next()? {
+            Token::Str(value) => Vdf::Str(value),
+            Token::Open => Vdf::Map(parse_vdf_map(tokens, true)?),
+            Token::Close => return None,
+        };
+
+        entries.push((key, value));
+    }
+}
+
+/// Every Steam install directory that exists for the current user.
+pub fn steam_roots() -> Vec<(Install, PathBuf)> {
+    let home = match std::env::var_os("HOME") {
+        Some(home) => PathBuf::from(home),
+        None => return Vec::new(),
+    };
+
+    let candidates = [
+        (Install::Native, home.join(".steam/steam")),
+        (Install::Native, home.join(".steam/root")),
+        (Install::Native, home.join(".local/share/Steam")),
+        (
+            Install::Flatpak,
+            home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+        ),
+        (
+            Install::Flatpak,
+            home.join(".var/app/com.valvesoftware.Steam/data/Steam"),
+        ),
+    ];
+
+    let mut roots: Vec<(Install, PathBuf)> = Vec::new();
+    for (install, path) in candidates {
+        let path = match path.canonicalize() {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+
+        if !roots.iter().any(|(_, p)| *p == path) {
+            roots.push((install, path));
+        }
+    }
+
+    roots
+}
+
+/// The library folders listed in a Steam root's `libraryfolders.vdf`, including the root itself.
+pub fn library_folders(root: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![root.to_path_buf()];
+
+    let vdf = ["steamapps/libraryfolders.vdf", "config/libraryfolders.vdf"]
+        .iter()
+        .filter_map(|p| std::fs::read_to_string(root.join(p)).ok())
+        .find_map(|text| parse_vdf(&text));
+
+    let folders = match vdf.as_ref().and_then(|v| v.get("libraryfolders")) {
+        Some(folders) => folders,
+        None => return libraries,
+    };
+
+    for (key, value) in folders.entries() {
+        if !key.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+
+        // Newer files nest a table with a `path` key, older ones map the index straight to the path.
+        let path = match value {
+            Vdf::Str(path) => Some(path.as_str()),
+            Vdf::Map(_) => value.get("path").and_then(Vdf::as_str),
+        };
+
+        if let Some(path) = path {
+            let path = PathBuf::from(path);
+            let path = path.canonicalize().unwrap_or(path);
+            if !libraries.contains(&path) {
+                libraries.push(path);
+            }
+        }
+    }
+
+    libraries
+}
+
+fn has_app_manifest(library: &Path, app_id: u32) -> bool {
+    let manifest = library
+        .join("steamapps")
+        .join(format!("appmanifest_{}.acf", app_id));
+
+    let text = match std::fs::read_to_string(manifest) {
+        Ok(text) => text,
+        Err(_) => return false,
+    };
+
+    parse_vdf(&text)
+        .as_ref()
+        .and_then(|v| v.get("AppState"))
+        .and_then(|s| s.get("appid"))
+        .and_then(Vdf::as_str)
+        .is_some_and(|id| id == app_id.to_string())
+}
+
+/// Finds every Proton prefix for BattleBit across all Steam installs and libraries.
+///
+/// Prefixes in libraries where the game is actually installed are listed first.
+pub fn find_prefixes() -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+
+    for (install, root) in steam_roots() {
+        for library in library_folders(&root) {
+            let user_reg = library
+                .join("steamapps/compatdata")
+                .join(BBR_APP_ID.to_string())
+                .join("pfx/user.reg");
+
+            if !user_reg.is_file() || candidates.iter().any(|c: &Candidate| c.user_reg == user_reg) {
+                continue;
+            }
+
+            candidates.push(Candidate {
+                install,
+                installed: has_app_manifest(&library, BBR_APP_ID),
+                user_reg,
+            });
+        }
+    }
+
+    candidates.sort_by_key(|c| !c.installed);
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIBRARY_FOLDERS: &str = r#"
+"libraryfolders"
+{
+	// A comment { with braces }
+	"contentstatsid"		"-123"
+	"0"
+	{
+		"path"		"LIBRARY_0"
+		"label"		"\"Main\" \\ drive"
+		"apps"
+		{
+			"228980"		"123"
+		}
+	}
+	"1"
+	{
+		"path"		"LIBRARY_1"
+		"apps"
+		{
+			"671860"		"456"
+		}
+	}
+}
+"#;
+
+    #[test]
+    fn parses_nested_blocks_and_escapes() {
+        let vdf = parse_vdf(LIBRARY_FOLDERS).unwrap();
+        let folders = vdf.get("LibraryFolders").unwrap();
+
+        assert_eq!(folders.get("contentstatsid").and_then(Vdf::as_str), Some("-123"));
+        assert_eq!(
+            folders.get("0").and_then(|f| f.get("label")).and_then(Vdf::as_str),
+            Some("\"Main\" \\ drive")
+        );
+        assert_eq!(
+            folders
+                .get("1")
+                .and_then(|f| f.get("apps"))
+                .and_then(|a| a.get("671860"))
+                .and_then(Vdf::as_str),
+            Some("456")
+        );
+        assert_eq!(folders.entries().len(), 3);
+    }
+
+    #[test]
+    fn rejects_unbalanced_blocks() {
+        assert!(parse_vdf("\"a\" { \"b\" \"c\"").is_none());
+        assert!(parse_vdf("\"a\" \"b\" }").is_none());
+        assert!(parse_vdf("\"a\"").is_none());
+    }
+
+    #[test]
+    fn lists_every_library() {
+        let dir = std::env::temp_dir().join(format!("bbr-settings-editor-{}-steam", std::process::id()));
+        let root = dir.join("root");
+        let libraries = [dir.join("library0"), dir.join("library1")];
+        std::fs::create_dir_all(root.join("steamapps")).unwrap();
+        for library in &libraries {
+            std::fs::create_dir_all(library.join("steamapps")).unwrap();
+        }
+
+        let vdf = LIBRARY_FOLDERS
+            .replace("LIBRARY_0", &libraries[0].display().to_string())
+            .replace("LIBRARY_1", &libraries[1].display().to_string());
+        std::fs::write(root.join("steamapps/libraryfolders.vdf"), vdf).unwrap();
+        std::fs::write(
+            libraries[1].join(format!("steamapps/appmanifest_{}.acf", BBR_APP_ID)),
+            format!("\"AppState\"\n{{\n\t\"appid\"\t\t\"{}\"\n}}\n", BBR_APP_ID),
+        )
+        .unwrap();
+
+        let found = library_folders(&root);
+        let installed: Vec<bool> = found.iter().map(|l| has_app_manifest(l, BBR_APP_ID)).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let canonical = |p: &Path| p.canonicalize().unwrap_or(p.to_path_buf());
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0], root);
+        assert_eq!(found[1..], libraries.iter().map(|l| canonical(l)).collect::<Vec<_>>());
+        assert_eq!(installed, vec![false, false, true]);
+    }
+}
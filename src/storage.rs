@@ -0,0 +1,400 @@
+use std::io;
+
+#[cfg(windows)]
+use winreg::{
+    enums::{HKEY_CURRENT_USER, KEY_READ, KEY_SET_VALUE},
+    RegKey,
+};
+
+use std::path::Path;
+
+use crate::{battlebit::RegValue, wine};
+#[cfg(windows)]
+use crate::battlebit::BBR_REG_SUBKEY;
+
+/// A place the raw Battlebit registry values can be read from and written to.
+pub trait Storage {
+    /// Every value currently stored, in storage order.
+    fn values(&self) -> io::Result<Vec<(String, RegValue)>>;
+
+    /// Creates or overwrites the value called `name`.
+    fn set_value(&mut self, name: &str, value: &RegValue) -> io::Result<()>;
+
+    /// Removes the value called `name`, failing if there is none.
+    fn delete_value(&mut self, name: &str) -> io::Result<()>;
+
+    /// Where the values are kept, e.g. the Windows registry or the path of a `user.reg`, to tell stores apart.
+    fn source(&self) -> String;
+
+    /// Persists any pending changes. Storages that write through immediately don't need this.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The live `HKEY_CURRENT_USER\SOFTWARE\BattleBitDevTeam\BattleBit` key.
+#[cfg(windows)]
+pub struct WindowsRegistry {
+    key: RegKey,
+}
+
+#[cfg(windows)]
+impl WindowsRegistry {
+    pub fn open() -> io::Result<Self> {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let key = hkcu.open_subkey_with_flags(BBR_REG_SUBKEY, KEY_READ | KEY_SET_VALUE)?;
+        Ok(WindowsRegistry { key })
+    }
+}
+
+#[cfg(windows)]
+impl Storage for WindowsRegistry {
+    fn values(&self) -> io::Result<Vec<(String, RegValue)>> {
+        self.key
+            .enum_values()
+            .map(|x| x.map(|(name, value)| (name, value.into())))
+            .collect()
+    }
+
+    fn set_value(&mut self, name: &str, value: &RegValue) -> io::Result<()> {
+        self.key.set_raw_value(name, &value.into())
+    }
+
+    fn delete_value(&mut self, name: &str) -> io::Result<()> {
+        self.key.delete_value(name)
+    }
+
+    fn source(&self) -> String {
+        "the Windows registry".to_string()
+    }
+}
+
+/// Values kept in memory only, useful for converting between formats and for tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    values: Vec<(String, RegValue)>,
+}
+
+impl MemoryStorage {
+    pub fn new(values: Vec<(String, RegValue)>) -> Self {
+        MemoryStorage { values }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn values(&self) -> io::Result<Vec<(String, RegValue)>> {
+        Ok(self.values.clone())
+    }
+
+    fn set_value(&mut self, name: &str, value: &RegValue) -> io::Result<()> {
+        match self.values.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.clone(),
+            None => self.values.push((name.to_string(), value.clone())),
+        }
+        Ok(())
+    }
+
+    fn delete_value(&mut self, name: &str) -> io::Result<()> {
+        let len = self.values.len();
+        self.values.retain(|(n, _)| n != name);
+
+        if self.values.len() == len {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No value named `{}`", name),
+            ));
+        }
+        Ok(())
+    }
+
+    fn source(&self) -> String {
+        "memory".to_string()
+    }
+}
+
+/// Opens the settings in a Wine/Proton prefix, given either the prefix itself or its `user.reg`.
+pub fn open_prefix(prefix: impl AsRef<Path>) -> io::Result<Box<dyn Storage>> {
+    let prefix = prefix.as_ref();
+    let path = if prefix.is_dir() {
+        prefix.join("user.reg")
+    } else {
+        prefix.to_path_buf()
+    };
+
+    let user_reg = wine::UserReg::open(path)?;
+    if !user_reg.has_section() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "`{}` does not contain any Battlebit settings",
+                user_reg.path().display()
+            ),
+        ));
+    }
+    Ok(Box::new(user_reg))
+}
+
+/// Opens the settings the game uses on this machine: the Windows registry, or the first Proton prefix found
+/// everywhere else.
+pub fn open_default() -> io::Result<Box<dyn Storage>> {
+    #[cfg(windows)]
+    {
+        Ok(Box::new(WindowsRegistry::open()?))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let candidate = crate::steam::find_prefixes().into_iter().next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "No BattleBit Proton prefix found, use `--prefix` to point at one",
+            )
+        })?;
+
+        tracing::info!("Using Proton prefix `{}`", candidate.user_reg.display());
+        open_prefix(candidate.user_reg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battlebit::{player_prefs_name, BattlebitType, Float, Int, RegType, State},
+        error::ErrorKind,
+        filters::{FilterVariant, Manual},
+    };
+
+    fn dword(value: i32) -> RegValue {
+        RegValue {
+            bytes: value.to_le_bytes().to_vec(),
+            vtype: RegType::REG_DWORD,
+        }
+    }
+
+    fn double(value: f64) -> RegValue {
+        RegValue {
+            bytes: value.to_le_bytes().to_vec(),
+            vtype: RegType::REG_DWORD,
+        }
+    }
+
+    fn binary(bytes: &[u8]) -> RegValue {
+        RegValue {
+            bytes: bytes.to_vec(),
+            vtype: RegType::REG_BINARY,
+        }
+    }
+
+    /// Drops the last byte of everything written to it, like a backend that mangles values.
+    struct Truncating(MemoryStorage);
+
+    impl Storage for Truncating {
+        fn values(&self) -> io::Result<Vec<(String, RegValue)>> {
+            self.0.values()
+        }
+
+        fn set_value(&mut self, name: &str, value: &RegValue) -> io::Result<()> {
+            let mut value = value.clone();
+            value.bytes.pop();
+            self.0.set_value(name, &value)
+        }
+
+        fn delete_value(&mut self, name: &str) -> io::Result<()> {
+            self.0.delete_value(name)
+        }
+
+        fn source(&self) -> String {
+            self.0.source()
+        }
+    }
+
+    fn state(values: Vec<(&str, RegValue)>) -> State {
+        let values = values.into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+        State::new(Box::new(MemoryStorage::new(values))).unwrap()
+    }
+
+    fn stored(state: &State, name: &str) -> Option<RegValue> {
+        state
+            .storage
+            .values()
+            .unwrap()
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn loads_and_saves_through_the_storage() {
+        let mut state = state(vec![
+            ("Jump_key_h123", dword(32)),
+            ("MasterVolume_float_h717095934", double(0.5)),
+        ]);
+        assert!(matches!(state.registry["MasterVolume"], BattlebitType::Float(Float(v)) if v == 0.5));
+
+        state
+            .from_toml_str(
+                r#"
+                [Jump_key]
+                typ = "key"
+                value = "LeftShift"
+
+                [MasterVolume]
+                typ = "float"
+                value = 0.25
+                "#,
+            )
+            .unwrap();
+        state.save_registry().unwrap();
+
+        assert_eq!(stored(&state, "Jump_key_h123"), Some(dword(304)));
+        assert_eq!(stored(&state, "MasterVolume_float_h717095934"), Some(double(0.25)));
+    }
+
+    #[test]
+    fn imports_are_saved_with_unity_layouts() {
+        let mut state = state(vec![
+            ("Jump_key_h123", dword(32)),
+            ("MasterVolume_float_h717095934", double(0.5)),
+            ("PlayerName_h99", binary(b"ABC\0")),
+        ]);
+
+        state
+            .from_toml_str(
+                r#"
+                [Jump_key]
+                typ = "key"
+                value = "LeftShift"
+
+                [MasterVolume]
+                typ = "float"
+                value = 0.25
+
+                [PlayerName]
+                typ = "str"
+                value = "xyz"
+
+                [Fullscreen]
+                typ = "bool"
+                value = true
+                "#,
+            )
+            .unwrap();
+        state.save_registry().unwrap();
+
+        assert_eq!(stored(&state, "Jump_key_h123"), Some(dword(304)));
+        assert_eq!(stored(&state, "MasterVolume_float_h717095934"), Some(double(0.25)));
+        assert_eq!(stored(&state, "PlayerName_h99"), Some(binary(b"xyz\0")));
+        assert_eq!(stored(&state, &player_prefs_name("Fullscreen_bool")), Some(dword(1)));
+    }
+
+    #[test]
+    fn untagged_values_keep_their_type_through_toml() {
+        let sz = RegValue {
+            bytes: crate::wine::encode_utf16("abc"),
+            vtype: RegType::REG_SZ,
+        };
+        let source = state(vec![
+            ("UnityGraphicsQuality_h1669003810", dword(2)),
+            ("unity.cloud_userid_h2665564582", sz.clone()),
+        ]);
+        assert!(matches!(source.registry["UnityGraphicsQuality"], BattlebitType::Int(Int(2))));
+
+        let config = source.to_toml();
+        for mut target in [state(vec![("UnityGraphicsQuality_h1669003810", dword(0))]), state(vec![])] {
+            target.from_toml_str(&config).unwrap();
+            target.save_registry().unwrap();
+
+            assert_eq!(stored(&target, "UnityGraphicsQuality_h1669003810"), Some(dword(2)));
+            assert_eq!(stored(&target, "unity.cloud_userid_h2665564582"), Some(sz.clone()));
+        }
+    }
+
+    #[test]
+    fn saving_reports_values_that_read_back_differently() {
+        let storage = Truncating(MemoryStorage::new(vec![("PlayerName_h99".to_string(), binary(b"ABC\0"))]));
+        let mut state = State::new(Box::new(storage)).unwrap();
+
+        state.from_toml_str("[PlayerName]\ntyp = \"str\"\nvalue = \"xyz\"\n").unwrap();
+        let error = state.save_registry().unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .contains("`PlayerName_h99` was stored as 3 bytes of REG_BINARY instead of 4 bytes of REG_BINARY"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn missing_settings_are_created_under_their_hashed_name() {
+        let mut state = state(vec![]);
+
+        state.from_toml_str("[Sensitivity]\ntyp = \"int\"\nvalue = 7\n").unwrap();
+        state.save_registry().unwrap();
+
+        assert_eq!(stored(&state, "Sensitivity_int_h150344100"), Some(dword(7)));
+    }
+
+    #[test]
+    fn reset_deletes_only_matching_settings() {
+        let mut state = state(vec![
+            ("Jump_key_h123", dword(32)),
+            ("MasterVolume_float_h717095934", double(0.5)),
+        ]);
+
+        let filters = [FilterVariant::Manual(Manual("Jump".to_string()))];
+        assert_eq!(state.reset(Some(&filters)), 1);
+        state.save_registry().unwrap();
+
+        assert_eq!(stored(&state, "Jump_key_h123"), None);
+        assert_eq!(stored(&state, "MasterVolume_float_h717095934"), Some(double(0.5)));
+    }
+
+    #[test]
+    fn ambiguous_settings_are_refused() {
+        let mut state = state(vec![("Jump_key_h1", dword(32)), ("Jump_key_h2", dword(33))]);
+
+        let error = state
+            .from_toml_str("[Jump_key]\ntyp = \"key\"\nvalue = \"A\"\n")
+            .unwrap_err();
+
+        assert_eq!(error.setting.as_deref(), Some("Jump_key"));
+        assert!(
+            matches!(&error.kind, ErrorKind::Ambiguous { candidates } if candidates == &["Jump_key_h1", "Jump_key_h2"]),
+            "{}",
+            error
+        );
+        assert!(state.updated_registry.is_empty());
+    }
+
+    #[test]
+    fn colors_that_cant_be_decoded_are_kept_raw() {
+        let short = RegValue {
+            bytes: vec![1, 2, 3, 4],
+            vtype: RegType::REG_BINARY,
+        };
+        let source = state(vec![
+            ("HitMarkerColor_r_h3534454626", short.clone()),
+            ("HitMarkerColor_g_h3534454647", double(0.5)),
+        ]);
+        assert!(matches!(source.registry["HitMarkerColor_r"], BattlebitType::Raw(_)));
+
+        let mut target = state(vec![("HitMarkerColor_r_h3534454626", double(1.0))]);
+        target.from_toml_str(&source.to_toml()).unwrap();
+        target.save_registry().unwrap();
+
+        assert_eq!(stored(&target, "HitMarkerColor_r_h3534454626"), Some(short));
+        assert_eq!(stored(&target, "HitMarkerColor_g_h3534454647"), Some(double(0.5)));
+    }
+
+    #[test]
+    fn memory_storage_refuses_to_delete_missing_values() {
+        let mut storage = MemoryStorage::new(vec![("Jump_key_h123".to_string(), dword(32))]);
+
+        storage.delete_value("Jump_key_h123").unwrap();
+        assert!(storage.values().unwrap().is_empty());
+        assert_eq!(storage.delete_value("Jump_key_h123").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
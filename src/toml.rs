@@ -4,30 +4,71 @@ use serde::{Deserialize, Serialize};
 use toml::Value;
 
 use crate::{
-    battlebit::{Axis, BattlebitType, Bool, Color, Float, Int, Key, State, Str},
+    battlebit::{Axis, BattlebitType, Bool, Color, Float, Int, Key, Raw, RegType, Resolved, State, Str},
+    color::{self, CHANNELS},
+    error::{ErrorKind, Location, SettingsError},
     filters::{Filter, FilterVariant},
 };
 
+/// A single setting in a TOML config.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Entry {
     pub typ: String,
     pub value: Value,
+    /// The registry type of a `raw` entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regtype: Option<String>,
+}
+
+/// Adds a setting to a config, merging color channels into one `{ r, g, b, a }` entry per color.
+fn insert_entry(state: &State, entries: &mut HashMap<String, Entry>, name: &str, value: &BattlebitType) {
+    // `raw` entries are imported by their registry name rather than resolved by setting name.
+    if let BattlebitType::Raw(raw) = value {
+        let regedit_name = match state.resolve_regedit_name(name, value) {
+            Ok(Resolved::Existing(regedit_name) | Resolved::New(regedit_name)) => regedit_name,
+            Err(_) => name.to_string(),
+        };
+        entries.insert(
+            regedit_name,
+            Entry {
+                typ: "raw".to_string(),
+                value: value.to_toml_value(),
+                regtype: Some(raw.0.vtype.name().to_string()),
+            },
+        );
+        return;
+    }
+
+    if let BattlebitType::Color(Color(channel, c)) = value {
+        let color = color::color_name(name);
+        let entry = entries.entry(color.to_string()).or_insert_with(|| Entry {
+            typ: "color".to_string(),
+            value: Value::Table(toml::value::Table::new()),
+            regtype: None,
+        });
+
+        if let (Value::Table(table), Some(channel)) = (&mut entry.value, CHANNELS.get(*channel as usize)) {
+            table.insert(channel.to_string(), Value::Float(*c));
+        }
+        return;
+    }
+
+    entries.insert(
+        name.to_string(),
+        Entry {
+            typ: value.to_reg_type_str().to_string(),
+            value: value.to_toml_value(),
+            regtype: None,
+        },
+    );
 }
 
 impl State {
+    /// Exports every decoded setting as a TOML config.
     pub fn to_toml(&self) -> String {
         let mut entries = HashMap::new();
-        for entry in self.registry.iter() {
-            let name = entry.0;
-            let typ = entry.1.to_reg_type_str();
-            let value = entry.1.to_toml_value();
-
-            let entry = Entry {
-                typ: typ.to_string(),
-                value: value.clone(),
-            };
-
-            entries.insert(name.clone(), entry);
+        for (name, value) in self.registry.iter() {
+            insert_entry(self, &mut entries, name, value);
         }
 
         let t = toml::Value::try_from(entries).expect("Failed to convert to TOML");
@@ -35,101 +76,284 @@ impl State {
         toml::to_string_pretty(&sorted).expect("Failed to serialize to TOML")
     }
 
+    /// Exports the settings matching `include` as a TOML config.
     pub fn to_filtered_toml(&self, include: Vec<FilterVariant>) -> String {
         let mut entries = HashMap::new();
         for entry in self.registry.iter() {
             let name = entry.0;
             let typ = entry.1.to_reg_type_str();
 
-            if !include.iter().any(|filter| filter.filter(&name, typ)) {
+            if !include.filter(name, typ) {
                 continue;
             }
 
-            let value = entry.1.to_toml_value();
+            insert_entry(self, &mut entries, name, entry.1);
+        }
+
+        let t = toml::Value::try_from(entries).expect("Failed to convert to TOML");
+        let sorted = sort_toml(&t);
+        toml::to_string_pretty(&sorted).expect("Failed to serialize to TOML")
+    }
+
+    /// Exports every registry value byte-for-byte, keyed by its registry name, so that importing it
+    /// restores the registry exactly, including values this tool doesn't understand.
+    pub fn to_lossless_toml(
+        &self,
+        include: Option<Vec<FilterVariant>>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut entries = HashMap::new();
+        for (name, value) in self.storage.values()? {
+            if let Some(include) = &include {
+                let (logical, typ) = BattlebitType::from_reg(name.clone(), value.clone())?;
+                if !include.filter(&logical, typ.to_reg_type_str()) {
+                    continue;
+                }
+            }
 
             let entry = Entry {
-                typ: typ.to_string(),
-                value: value.clone(),
+                typ: "raw".to_string(),
+                regtype: Some(value.vtype.name().to_string()),
+                value: Value::String(Raw(value).to_hex()),
             };
 
-            entries.insert(name.clone(), entry);
+            entries.insert(name, entry);
         }
 
-        let t = toml::Value::try_from(entries).expect("Failed to convert to TOML");
+        let t = toml::Value::try_from(entries)?;
         let sorted = sort_toml(&t);
-        toml::to_string_pretty(&sorted).expect("Failed to serialize to TOML")
+        Ok(toml::to_string_pretty(&sorted)?)
     }
 
-    pub fn from_toml_str(&mut self, toml: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let toml = toml::from_str(toml)?;
-        self.from_toml(toml)
+    /// Queues every setting in a TOML config to be written by [`State::save_registry`].
+    ///
+    /// Nothing is queued if any setting fails to parse or is ambiguous.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_toml_str(&mut self, toml: &str) -> Result<(), SettingsError> {
+        self.apply_entries(parse_entries(located_tables(toml)?)?)
     }
 
-    pub fn from_toml(&mut self, toml: Value) -> Result<(), Box<dyn std::error::Error>> {
-        let entries: HashMap<String, Entry> = toml.try_into()?;
-
-        let mut registry = HashMap::new();
-
-        for (name, entry) in entries {
-            let value = match entry.typ.as_str() {
-                "int" => BattlebitType::Int(Int(entry
-                    .value
-                    .as_integer()
-                    .ok_or("Invalid integer value")?
-                    as i32)),
-                "float" => BattlebitType::Float(Float(
-                    entry.value.as_float().ok_or("Invalid float value")?,
-                )),
-                "bool" => {
-                    BattlebitType::Bool(Bool(entry.value.as_bool().ok_or("Invalid bool value")?))
-                }
-                "axis" => BattlebitType::Axis(Axis(
-                    entry.value.as_integer().ok_or("Invalid axis value")? as i32,
-                )),
-                "color" => {
-                    let last = name.chars().last().ok_or("Invalid color name")?;
-                    match last {
-                        'r' => BattlebitType::Color(Color(
-                            0,
-                            entry.value.as_float().ok_or("Invalid color value")? as f64,
-                        )),
-                        'g' => BattlebitType::Color(Color(
-                            1,
-                            entry.value.as_float().ok_or("Invalid color value")? as f64,
-                        )),
-                        'b' => BattlebitType::Color(Color(
-                            2,
-                            entry.value.as_float().ok_or("Invalid color value")? as f64,
-                        )),
-                        'a' => BattlebitType::Color(Color(
-                            3,
-                            entry.value.as_float().ok_or("Invalid color value")? as f64,
-                        )),
-                        _ => return Err("Invalid color name".into()),
-                    }
-                }
-                "key" => BattlebitType::Key(Key::from_key_ascii(
-                    entry.value.as_str().ok_or("Invalid key value")?,
-                )?),
-                "str" => BattlebitType::Str(Str(entry
-                    .value
-                    .as_str()
-                    .ok_or("Invalid string value")?
-                    .to_string())),
-                _ => return Err("Invalid type".into()),
-            };
+    /// Like [`State::from_toml_str`], for a config that is already parsed.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_toml(&mut self, toml: Value) -> Result<(), SettingsError> {
+        self.apply_entries(parse_entries(tables(toml)?)?)
+    }
 
-            registry.insert(name, value);
+    fn apply_entries(&mut self, (registry, raw): Entries) -> Result<(), SettingsError> {
+        // Check everything up front so an ambiguous name doesn't leave a half applied import behind.
+        let mut ambiguous: Vec<SettingsError> = registry
+            .iter()
+            .filter_map(|(name, value)| self.resolve_regedit_name(name, value).err())
+            .collect();
+
+        if ambiguous.len() > 1 {
+            ambiguous.sort_by(|a, b| a.setting.cmp(&b.setting));
+            return Err(SettingsError::new(ErrorKind::Multiple(ambiguous)));
+        }
+        if let Some(error) = ambiguous.pop() {
+            return Err(error);
         }
 
         for (name, value) in registry {
-            self.update_registry(&name, value);
+            self.update_registry(&name, value)?;
+        }
+
+        for (name, value) in raw {
+            self.updated_registry.insert(name, BattlebitType::Raw(value));
         }
 
         Ok(())
     }
 }
 
+/// The settings of a config by name, and its `raw` values by registry name.
+type Entries = (HashMap<String, BattlebitType>, Vec<(String, Raw)>);
+
+/// Every entry of a config, without knowing where in the file they came from.
+fn tables(toml: Value) -> Result<Vec<(String, Value, Option<Location>)>, SettingsError> {
+    match toml {
+        Value::Table(table) => Ok(table.into_iter().map(|(name, value)| (name, value, None)).collect()),
+        _ => Err(SettingsError::new(ErrorKind::Syntax("Expected a table of settings".to_string()))),
+    }
+}
+
+/// Every entry of a config, along with the line it starts on.
+fn located_tables(toml: &str) -> Result<Vec<(String, Value, Option<Location>)>, SettingsError> {
+    let table: HashMap<toml::Spanned<String>, Value> = toml::from_str(toml).map_err(|e| {
+        let location = e.span().map(|span| Location::at_offset(toml, span.start));
+        SettingsError::new(ErrorKind::Syntax(e.message().to_string())).at(location)
+    })?;
+
+    // In file order, so the first problem reported is the first one in the file.
+    let mut table: Vec<_> = table.into_iter().collect();
+    table.sort_by_key(|(name, _)| name.span().start);
+
+    Ok(table
+        .into_iter()
+        .map(|(name, value)| {
+            let location = Location::at_offset(toml, name.span().start);
+            (name.into_inner(), value, Some(location))
+        })
+        .collect())
+}
+
+fn parse_entries(tables: Vec<(String, Value, Option<Location>)>) -> Result<Entries, SettingsError> {
+    let mut registry = HashMap::new();
+    let mut raw = Vec::new();
+
+    for (name, table, location) in tables {
+        let fail = |e: SettingsError| e.for_setting(&name).at(location.clone());
+        let entry: Entry = table
+            .try_into()
+            .map_err(|e: toml::de::Error| fail(SettingsError::new(ErrorKind::Syntax(e.message().to_string()))))?;
+
+        match parse_entry(&name, entry).map_err(fail)? {
+            Parsed::Setting(value) => {
+                registry.insert(name, value);
+            }
+            Parsed::Color(channels) => registry.extend(channels),
+            Parsed::Raw(value) => raw.push((name, value)),
+        }
+    }
+
+    Ok((registry, raw))
+}
+
+enum Parsed {
+    Setting(BattlebitType),
+    /// A whole color, expanded into its channels' settings.
+    Color(Vec<(String, BattlebitType)>),
+    /// Raw entries are keyed by their registry name already, so they skip name resolution.
+    Raw(Raw),
+}
+
+fn parse_entry(name: &str, entry: Entry) -> Result<Parsed, SettingsError> {
+    let value = &entry.value;
+    let invalid = |expected| SettingsError::invalid_value(expected, value);
+
+    let setting = match entry.typ.as_str() {
+        "raw" => {
+            let vtype = match &entry.regtype {
+                Some(regtype) => RegType::from_name(regtype)
+                    .ok_or_else(|| SettingsError::invalid_value("a registry type such as REG_DWORD", regtype))?,
+                None => return Err(SettingsError::invalid_value("a `regtype` for a raw value", "none")),
+            };
+            let hex = value.as_str().ok_or_else(|| invalid("a string of hex digits"))?;
+            return Ok(Parsed::Raw(Raw::from_hex(vtype, hex)?));
+        }
+        "int" => BattlebitType::Int(Int(value.as_integer().ok_or_else(|| invalid("an int"))? as i32)),
+        "float" => BattlebitType::Float(Float(value.as_float().ok_or_else(|| invalid("a float"))?)),
+        "bool" => BattlebitType::Bool(Bool(value.as_bool().ok_or_else(|| invalid("a bool"))?)),
+        "axis" => BattlebitType::Axis(Axis(value.as_integer().ok_or_else(|| invalid("an int"))? as i32)),
+        "color" => return parse_color(name, value).map(Parsed::Color),
+        "key" => BattlebitType::Key(Key::from_name(
+            value.as_str().ok_or_else(|| invalid("a string"))?,
+        )?),
+        "str" => BattlebitType::Str(Str(value.as_str().ok_or_else(|| invalid("a string"))?.to_string())),
+        typ => return Err(SettingsError::new(ErrorKind::UnknownType(typ.to_string()))),
+    };
+
+    Ok(Parsed::Setting(setting))
+}
+
+const COLOR_EXPECTED: &str =
+    "a color such as `#FF3300`, `rgba(255, 51, 0, 0.5)`, `hsl(12, 100%, 50%)`, `red` or an `{ r, g, b, a }` table";
+
+/// Expands a color entry into the settings of its channels.
+///
+/// A single channel, e.g. `HitMarkerColor_r = 0.5`, is still accepted as older configs were written that way.
+fn parse_color(name: &str, value: &Value) -> Result<Vec<(String, BattlebitType)>, SettingsError> {
+    let channel = |c: char, value: f64| {
+        let index = CHANNELS.iter().position(|&x| x == c).unwrap_or_default() as i32;
+        (format!("{}_{}", name, c), BattlebitType::Color(Color(index, value)))
+    };
+    let float = |value: &Value| value.as_float().or_else(|| value.as_integer().map(|i| i as f64));
+
+    match value {
+        Value::Float(_) | Value::Integer(_) => {
+            let index = name
+                .rsplit_once('_')
+                .and_then(|(_, c)| CHANNELS.iter().position(|x| x.to_string() == c))
+                .ok_or_else(|| SettingsError::invalid_value(COLOR_EXPECTED, value))?;
+            Ok(vec![(name.to_string(), BattlebitType::Color(Color(index as i32, float(value).unwrap())))])
+        }
+        Value::Table(table) => {
+            if let Some(key) = table.keys().find(|k| !CHANNELS.iter().any(|c| c.to_string() == **k)) {
+                return Err(SettingsError::invalid_value(
+                    "a color table with only r, g, b and a",
+                    format!("`{}`", key),
+                ));
+            }
+
+            CHANNELS
+                .iter()
+                .filter_map(|&c| table.get(&c.to_string()).map(|v| (c, v)))
+                .map(|(c, v)| {
+                    let v = float(v).ok_or_else(|| SettingsError::invalid_value("a float from 0 to 1", v))?;
+                    Ok(channel(c, v))
+                })
+                .collect()
+        }
+        Value::String(s) => {
+            let rgba = color::parse_color(s).ok_or_else(|| SettingsError::invalid_value(COLOR_EXPECTED, value))?;
+            Ok(CHANNELS.iter().zip(rgba).map(|(&c, v)| channel(c, v)).collect())
+        }
+        _ => Err(SettingsError::invalid_value(COLOR_EXPECTED, value)),
+    }
+}
+
+/// Reads the settings in a config by name, decoding any `raw` values, without touching the registry.
+pub fn settings_from_toml(toml: &str) -> Result<HashMap<String, BattlebitType>, SettingsError> {
+    let (mut registry, raw) = parse_entries(located_tables(toml)?)?;
+
+    for (name, value) in raw {
+        let (name, value) = BattlebitType::from_reg(name, value.0)?;
+        registry.insert(name, value);
+    }
+
+    Ok(registry)
+}
+
+/// Rewrites every `key` entry in a config with `f`.
+///
+/// The config is edited in place, so everything but the keys, including comments and the order of the entries,
+/// stays as it was.
+pub fn map_keys(toml: &str, mut f: impl FnMut(&str, Key) -> Key) -> Result<String, SettingsError> {
+    let mut document = toml
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| SettingsError::new(ErrorKind::Syntax(e.message().to_string())))?;
+
+    for (name, table, location) in located_tables(toml)? {
+        let fail = |e: SettingsError| e.for_setting(&name).at(location.clone());
+        let entry: Entry = table
+            .try_into()
+            .map_err(|e: toml::de::Error| fail(SettingsError::new(ErrorKind::Syntax(e.message().to_string()))))?;
+
+        if entry.typ != "key" {
+            continue;
+        }
+
+        let key = entry
+            .value
+            .as_str()
+            .ok_or_else(|| SettingsError::invalid_value("a string", &entry.value))
+            .and_then(Key::from_name)
+            .map_err(fail)?;
+
+        let value = document
+            .get_mut(&name)
+            .and_then(|entry| entry.get_mut("value"))
+            .and_then(|value| value.as_value_mut());
+        if let Some(value) = value {
+            // Keeps the whitespace and comments around the value.
+            let decor = value.decor().clone();
+            *value = f(&name, key).name().into();
+            *value.decor_mut() = decor;
+        }
+    }
+
+    Ok(document.to_string())
+}
+
 fn sort_toml(toml: &Value) -> Value {
     match toml {
         Value::Table(table) => {
@@ -0,0 +1,641 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    battlebit::{RegType, RegValue, BBR_REG_SUBKEY},
+    storage::Storage,
+};
+
+// Seconds between 1601-01-01 (FILETIME epoch) and 1970-01-01.
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+// `user.reg` paths are relative to HKEY_CURRENT_USER and written with Wine's casing.
+const BBR_REG_SUBKEY_USER: &str = "Software\\BattleBitDevTeam\\BattleBit";
+
+// Wine wraps `hex:` data once a line grows past this many columns.
+const HEX_LINE_WIDTH: usize = 76;
+
+struct Entry {
+    name: String,
+    value: Option<RegValue>,
+    // The original text of the entry, kept so untouched values are written back verbatim.
+    raw: Option<String>,
+}
+
+struct Section {
+    header: String,
+    meta: Vec<String>,
+    entries: Vec<Entry>,
+    dirty: bool,
+}
+
+/// The BattleBit section of a Wine/Proton prefix's `user.reg`.
+///
+/// Everything outside of `[Software\\BattleBitDevTeam\\BattleBit]` is kept as-is,
+/// so saving only ever touches the lines belonging to that key.
+///
+/// Wine keeps the registry in memory while a prefix is running and writes all of it back when its wineserver
+/// exits, so changes made while the game (or anything else in the prefix) is running are overwritten.
+pub struct UserReg {
+    path: PathBuf,
+    head: String,
+    section: Option<Section>,
+    tail: String,
+}
+
+impl UserReg {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let contents = std::fs::read_to_string(&path)?;
+
+        let mut user_reg = UserReg {
+            path,
+            head: String::new(),
+            section: None,
+            tail: String::new(),
+        };
+
+        let mut lines = contents.split_inclusive('\n').peekable();
+
+        while let Some(line) = lines.next() {
+            if is_bbr_header(line) {
+                let mut section = Section {
+                    header: line.trim_end().to_string(),
+                    meta: Vec::new(),
+                    entries: Vec::new(),
+                    dirty: false,
+                };
+
+                while let Some(line) = lines.peek() {
+                    if line.starts_with('[') {
+                        break;
+                    }
+
+                    let mut raw = lines.next().unwrap().to_string();
+                    while raw.trim_end().ends_with('\\') {
+                        match lines.next() {
+                            Some(next) => raw.push_str(next),
+                            None => break,
+                        }
+                    }
+
+                    let text = raw.trim_end();
+                    if text.is_empty() {
+                        continue;
+                    }
+
+                    if text.starts_with('#') || text.starts_with(';') {
+                        section.meta.push(text.to_string());
+                        continue;
+                    }
+
+                    match parse_entry(text) {
+                        Some((name, value)) => section.entries.push(Entry {
+                            name,
+                            value: Some(value),
+                            raw: Some(text.to_string()),
+                        }),
+                        None => {
+                            tracing::warn!("Failed to parse `user.reg` line: {}", text);
+                            section.entries.push(Entry {
+                                name: String::new(),
+                                value: None,
+                                raw: Some(text.to_string()),
+                            });
+                        }
+                    }
+                }
+
+                user_reg.section = Some(section);
+                user_reg.tail = lines.collect();
+                break;
+            }
+
+            user_reg.head.push_str(line);
+        }
+
+        Ok(user_reg)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn has_section(&self) -> bool {
+        self.section.is_some()
+    }
+}
+
+impl Storage for UserReg {
+    fn values(&self) -> io::Result<Vec<(String, RegValue)>> {
+        Ok(self
+            .section
+            .iter()
+            .flat_map(|s| s.entries.iter())
+            .filter_map(|e| e.value.clone().map(|v| (e.name.clone(), v)))
+            .collect())
+    }
+
+    fn set_value(&mut self, name: &str, value: &RegValue) -> io::Result<()> {
+        let section = self.section.get_or_insert_with(|| Section {
+            header: format!("[{}]", escape_key_path(BBR_REG_SUBKEY_USER)),
+            meta: Vec::new(),
+            entries: Vec::new(),
+            dirty: true,
+        });
+        section.dirty = true;
+
+        match section
+            .entries
+            .iter_mut()
+            .find(|e| e.value.is_some() && e.name == name)
+        {
+            Some(entry) => {
+                entry.value = Some(value.clone());
+                entry.raw = None;
+            }
+            None => section.entries.push(Entry {
+                name: name.to_string(),
+                value: Some(value.clone()),
+                raw: None,
+            }),
+        }
+        Ok(())
+    }
+
+    fn delete_value(&mut self, name: &str) -> io::Result<()> {
+        let section = self.section.as_mut().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No Battlebit section in `user.reg`")
+        })?;
+
+        let len = section.entries.len();
+        section
+            .entries
+            .retain(|e| e.value.is_none() || e.name != name);
+
+        if section.entries.len() == len {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No value named `{}`", name),
+            ));
+        }
+        section.dirty = true;
+        Ok(())
+    }
+
+    fn source(&self) -> String {
+        // Canonical, so the same prefix is recognized however it was given.
+        std::fs::canonicalize(&self.path)
+            .unwrap_or_else(|_| self.path.clone())
+            .display()
+            .to_string()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let dirty = self.section.as_ref().is_some_and(|s| s.dirty);
+        if !dirty {
+            return Ok(());
+        }
+
+        // Written next to it and renamed over it, so a crash or a full disk can't leave `user.reg` truncated.
+        let temp = self.path.with_extension("reg.tmp");
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp, &self.path)?;
+
+        // Parsed again, so what is read back afterwards is what ended up in the file rather than what was meant to.
+        *self = UserReg::open(&self.path)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for UserReg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.head)?;
+
+        let section = match &self.section {
+            Some(s) => s,
+            None => return f.write_str(&self.tail),
+        };
+
+        if !self.head.is_empty() && !self.head.ends_with("\n\n") {
+            f.write_str("\n")?;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        if section.dirty {
+            let key = section.header.split(']').next().unwrap_or_default();
+            writeln!(f, "{}] {}", key, now.as_secs())?;
+        } else {
+            writeln!(f, "{}", section.header)?;
+        }
+
+        let mut wrote_time = false;
+        for meta in &section.meta {
+            if section.dirty && meta.starts_with("#time=") {
+                writeln!(f, "#time={:x}", filetime(now))?;
+                wrote_time = true;
+            } else {
+                writeln!(f, "{}", meta)?;
+            }
+        }
+        if section.dirty && !wrote_time && section.meta.is_empty() {
+            writeln!(f, "#time={:x}", filetime(now))?;
+        }
+
+        for entry in &section.entries {
+            match (&entry.raw, &entry.value) {
+                (Some(raw), _) => writeln!(f, "{}", raw)?,
+                (None, Some(value)) => writeln!(f, "{}", format_entry(&entry.name, value))?,
+                (None, None) => {}
+            }
+        }
+
+        if !self.tail.is_empty() {
+            f.write_str("\n")?;
+        }
+        f.write_str(&self.tail)
+    }
+}
+
+fn is_bbr_header(line: &str) -> bool {
+    let line = line.trim_end();
+    if !line.starts_with('[') {
+        return false;
+    }
+
+    let key = match line[1..].split(']').next() {
+        Some(k) => k,
+        None => return false,
+    };
+
+    unescape_key_path(key).eq_ignore_ascii_case(BBR_REG_SUBKEY)
+}
+
+fn filetime(since_epoch: std::time::Duration) -> u64 {
+    (since_epoch.as_secs() + FILETIME_UNIX_OFFSET) * 10_000_000
+        + since_epoch.subsec_nanos() as u64 / 100
+}
+
+fn escape_key_path(path: &str) -> String {
+    path.replace('\\', "\\\\")
+}
+
+fn unescape_key_path(path: &str) -> String {
+    path.replace("\\\\", "\\")
+}
+
+fn parse_entry(text: &str) -> Option<(String, RegValue)> {
+    let (name, data) = if let Some(rest) = text.strip_prefix('@') {
+        (String::new(), rest.strip_prefix('=')?)
+    } else {
+        let (name, rest) = parse_quoted(text)?;
+        (name, rest.strip_prefix('=')?)
+    };
+
+    Some((name, parse_data(data)?))
+}
+
+/// Parses a quoted, escaped string at the start of `text`, returning it along with the rest of the input.
+///
+/// Escapes are UTF-16 code units, like the strings Wine keeps, so a surrogate pair escaped as two `\x` units
+/// decodes to the one character it stands for.
+pub(crate) fn parse_quoted(text: &str) -> Option<(String, &str)> {
+    let body = text.strip_prefix('"')?;
+    let mut out: Vec<u16> = Vec::new();
+    let mut chars = body.char_indices().peekable();
+
+    let push = |out: &mut Vec<u16>, c: char| out.extend(c.encode_utf16(&mut [0; 2]).iter());
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((String::from_utf16_lossy(&out), &body[i + 1..])),
+            '\\' => {
+                let (_, escaped) = chars.next()?;
+                match escaped {
+                    'n' => push(&mut out, '\n'),
+                    'r' => push(&mut out, '\r'),
+                    't' => push(&mut out, '\t'),
+                    'a' => push(&mut out, '\x07'),
+                    'b' => push(&mut out, '\x08'),
+                    'e' => push(&mut out, '\x1b'),
+                    'f' => push(&mut out, '\x0c'),
+                    'v' => push(&mut out, '\x0b'),
+                    'x' => {
+                        let mut unit = 0u16;
+                        for _ in 0..4 {
+                            match chars.peek().and_then(|(_, c)| c.to_digit(16)) {
+                                Some(d) => {
+                                    unit = unit * 16 + d as u16;
+                                    chars.next();
+                                }
+                                None => break,
+                            }
+                        }
+                        out.push(unit);
+                    }
+                    '0'..='7' => {
+                        let mut unit = escaped.to_digit(8)? as u16;
+                        for _ in 0..2 {
+                            match chars.peek().and_then(|(_, c)| c.to_digit(8)) {
+                                Some(d) => {
+                                    unit = unit * 8 + d as u16;
+                                    chars.next();
+                                }
+                                None => break,
+                            }
+                        }
+                        out.push(unit);
+                    }
+                    other => push(&mut out, other),
+                }
+            }
+            _ => push(&mut out, c),
+        }
+    }
+
+    None
+}
+
+/// Quotes and escapes `s` the same way Wine does when it writes `user.reg`.
+///
+/// Anything outside of printable ASCII is written as `\x` and its UTF-16 code units, padded to four digits
+/// when a hex digit follows so it can't be read as part of the escape.
+pub(crate) fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    let units: Vec<u16> = s.encode_utf16().collect();
+
+    for (i, &unit) in units.iter().enumerate() {
+        match unit {
+            0x5c => out.push_str("\\\\"),
+            0x22 => out.push_str("\\\""),
+            0x0a => out.push_str("\\n"),
+            0x0d => out.push_str("\\r"),
+            0x09 => out.push_str("\\t"),
+            0x20..=0x7e => out.push(unit as u8 as char),
+            _ => {
+                let next_is_hex = units
+                    .get(i + 1)
+                    .is_some_and(|&next| next < 0x80 && (next as u8).is_ascii_hexdigit());
+                if next_is_hex {
+                    out.push_str(&format!("\\x{:04x}", unit));
+                } else {
+                    out.push_str(&format!("\\x{:x}", unit));
+                }
+            }
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// Parses the data half of a `name=data` line (`"str"`, `str(n):"str"`, `dword:`, `hex:` or `hex(n):`).
+pub(crate) fn parse_data(data: &str) -> Option<RegValue> {
+    if data.starts_with('"') {
+        let (s, _) = parse_quoted(data)?;
+        return Some(RegValue {
+            bytes: encode_utf16(&s),
+            vtype: RegType::REG_SZ,
+        });
+    }
+
+    if let Some(rest) = data.strip_prefix("str(") {
+        let (typ, rest) = rest.split_once("):")?;
+        let (s, _) = parse_quoted(rest)?;
+        return Some(RegValue {
+            bytes: encode_utf16(&s),
+            vtype: RegType::from_u32(u32::from_str_radix(typ, 16).ok()?)?,
+        });
+    }
+
+    if let Some(rest) = data.strip_prefix("dword:") {
+        let value = u32::from_str_radix(rest.trim(), 16).ok()?;
+        return Some(RegValue {
+            bytes: value.to_le_bytes().to_vec(),
+            vtype: RegType::REG_DWORD,
+        });
+    }
+
+    let (vtype, rest) = if let Some(rest) = data.strip_prefix("hex:") {
+        (RegType::REG_BINARY, rest)
+    } else if let Some(rest) = data.strip_prefix("hex(") {
+        let (typ, rest) = rest.split_once("):")?;
+        (RegType::from_u32(u32::from_str_radix(typ, 16).ok()?)?, rest)
+    } else {
+        return None;
+    };
+
+    Some(RegValue {
+        bytes: parse_hex_list(rest)?,
+        vtype,
+    })
+}
+
+/// Parses a comma separated list of hex bytes, which may be split with `\` line continuations.
+pub(crate) fn parse_hex_list(list: &str) -> Option<Vec<u8>> {
+    list.split(',')
+        .map(|b| b.trim().trim_start_matches('\\').trim())
+        .filter(|b| !b.is_empty())
+        .map(|b| u8::from_str_radix(b, 16).ok())
+        .collect()
+}
+
+fn format_entry(name: &str, value: &RegValue) -> String {
+    let name = if name.is_empty() {
+        String::from("@")
+    } else {
+        quote(name)
+    };
+
+    match value.vtype {
+        RegType::REG_DWORD if value.bytes.len() == 4 => {
+            let dword = u32::from_le_bytes(value.bytes[..4].try_into().unwrap());
+            format!("{}=dword:{:08x}", name, dword)
+        }
+        RegType::REG_SZ | RegType::REG_EXPAND_SZ | RegType::REG_MULTI_SZ => {
+            match decode_utf16(&value.bytes) {
+                Some(s) if value.vtype == RegType::REG_SZ => format!("{}={}", name, quote(&s)),
+                Some(s) => format!("{}=str({:x}):{}", name, value.vtype as u32, quote(&s)),
+                None => format_hex(&name, value),
+            }
+        }
+        _ => format_hex(&name, value),
+    }
+}
+
+/// Formats `value` as `name=hex:...`, wrapping long data with `\` continuations. `name` must already be quoted.
+pub(crate) fn format_hex(name: &str, value: &RegValue) -> String {
+    let mut out = match value.vtype {
+        RegType::REG_BINARY => format!("{}=hex:", name),
+        typ => format!("{}=hex({:x}):", name, typ as u32),
+    };
+
+    let mut column = out.len();
+    for (i, byte) in value.bytes.iter().enumerate() {
+        out.push_str(&format!("{:02x}", byte));
+        column += 2;
+
+        if i + 1 < value.bytes.len() {
+            out.push(',');
+            column += 1;
+
+            if column > HEX_LINE_WIDTH {
+                out.push_str("\\\n  ");
+                column = 2;
+            }
+        }
+    }
+
+    out
+}
+
+/// Encodes `s` as NUL terminated UTF-16LE, the raw layout of a `REG_SZ` value.
+pub(crate) fn encode_utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|c| c.to_le_bytes())
+        .collect()
+}
+
+/// Decodes raw `REG_SZ` bytes, dropping the trailing NUL terminator.
+pub(crate) fn decode_utf16(bytes: &[u8]) -> Option<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    let units = match units.last() {
+        Some(0) => &units[..units.len() - 1],
+        _ => &units[..],
+    };
+
+    String::from_utf16(units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER_REG: &str = concat!(
+        "WINE REGISTRY Version 2\n",
+        ";; All keys relative to \\\\User\\\\S-1-5-21-0-0-0-1000\n\n",
+        "#arch=win64\n\n",
+        "[Software\\\\BattleBitDevTeam\\\\BattleBit] 1700000000\n",
+        "#time=1d9f0e0f0e0f0e0\n",
+        "\"MasterVolume_float_h717095934\"=hex(4):00,00,00,00,00,00,e0,3f\n",
+        "\"Jump_key_h123\"=dword:00000020\n",
+        "\"Big_h1\"=hex:00,01,02,03,04,05,06,07,08,09,0a,0b,0c,0d,0e,0f,10,11,12,13,14,\\\n",
+        "  15,16,17,18\n",
+        "\"unity.cloud_userid_h1\"=\"a\\\"b\\x2713c\"\n\n",
+        "[Software\\\\Wine] 1700000000\n",
+        "#time=1\n",
+        "\"Version\"=\"win10\"\n",
+    );
+
+    /// Writes `contents` to a `user.reg` of its own, so tests can run in parallel.
+    fn user_reg(test: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bbr-settings-editor-{}-{}", std::process::id(), test));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("user.reg");
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_every_kind_of_value() {
+        let path = user_reg("reads", USER_REG);
+        let values = UserReg::open(&path).unwrap().values().unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(
+            values,
+            vec![
+                (
+                    "MasterVolume_float_h717095934".to_string(),
+                    RegValue {
+                        bytes: 0.5f64.to_le_bytes().to_vec(),
+                        vtype: RegType::REG_DWORD,
+                    },
+                ),
+                (
+                    "Jump_key_h123".to_string(),
+                    RegValue {
+                        bytes: 32u32.to_le_bytes().to_vec(),
+                        vtype: RegType::REG_DWORD,
+                    },
+                ),
+                (
+                    "Big_h1".to_string(),
+                    RegValue {
+                        bytes: (0..25).collect(),
+                        vtype: RegType::REG_BINARY,
+                    },
+                ),
+                (
+                    "unity.cloud_userid_h1".to_string(),
+                    RegValue {
+                        bytes: encode_utf16("a\"b✓c"),
+                        vtype: RegType::REG_SZ,
+                    },
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoting_round_trips() {
+        for s in ["é1 ✓a \u{1}7", "\u{1F600}a\u{1F600}", "\u{7f}\u{80}F", "tab\there \"quoted\" C:\\"] {
+            let quoted = quote(s);
+            assert_eq!(parse_quoted(&quoted), Some((s.to_string(), "")), "{}", quoted);
+        }
+
+        assert_eq!(quote("é1 ✓a"), "\"\\x00e91 \\x2713a\"");
+        assert_eq!(quote("\u{1F600}"), "\"\\xd83d\\xde00\"");
+    }
+
+    #[test]
+    fn saving_round_trips_and_leaves_other_keys_alone() {
+        let path = user_reg("saves", USER_REG);
+        let mut user_reg = UserReg::open(&path).unwrap();
+
+        let name = RegValue {
+            bytes: "Player ✓\0".as_bytes().to_vec(),
+            vtype: RegType::REG_BINARY,
+        };
+        let id = RegValue {
+            bytes: encode_utf16("é1 ✓a \u{1}7 \u{1F600}"),
+            vtype: RegType::REG_SZ,
+        };
+        let mut expected = user_reg.values().unwrap();
+        expected.retain(|(n, _)| n != "Jump_key_h123" && n != "unity.cloud_userid_h1");
+        expected.push(("unity.cloud_userid_h1".to_string(), id.clone()));
+        expected.push(("PlayerName_h99".to_string(), name.clone()));
+
+        user_reg.set_value("PlayerName_h99", &name).unwrap();
+        user_reg.set_value("unity.cloud_userid_h1", &id).unwrap();
+        user_reg.delete_value("Jump_key_h123").unwrap();
+        user_reg.flush().unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with("WINE REGISTRY Version 2\n"));
+        assert!(written.ends_with("[Software\\\\Wine] 1700000000\n#time=1\n\"Version\"=\"win10\"\n"));
+        assert!(!path.with_extension("reg.tmp").exists());
+
+        let mut values = UserReg::open(&path).unwrap().values().unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(values, expected);
+    }
+}